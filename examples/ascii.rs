@@ -6,7 +6,8 @@ use sdl2::keyboard::Keycode;
 use sdl2::pixels::Color;
 
 use mray::algebra::Point2f;
-use mray::canvas::Canvas;
+use mray::canvas::{Canvas, PixelFormat};
+use mray::gamma::Gamma;
 
 fn find_sdl_gl_driver() -> Option<u32> {
     for (index, item) in sdl2::render::drivers().enumerate() {
@@ -19,21 +20,18 @@ fn find_sdl_gl_driver() -> Option<u32> {
 pub struct Console {
     font_size: (i32, i32),
     scaler: f32,
-    pub canvas: Canvas,
 }
 
 impl Console {
-    pub fn new(size: (i32, i32)) -> Console {
-        let font_size = (30, 40);
+    pub fn new(font_size: (i32, i32)) -> Console {
         Console {
             font_size,
             scaler: 40.,
-            canvas: Canvas::new((size.0 * font_size.0, size.1 * font_size.1)),
         }
     }
 
-    pub fn render(&mut self) {
-        self.canvas.flush();
+    pub fn render(&self, canvas: &mut Canvas) {
+        canvas.flush();
         for y in 0..16_u8 {
             for x in 0..16_u8 {
                 let ch: u8 = y * 16 + x;
@@ -41,14 +39,14 @@ impl Console {
                     .shift(Point2f::from_floats(-0.5, -0.5))
                     .shear(-0.25)
                     .shift(Point2f::from_floats(0.5, 0.5))
-                    .zoom(self.scaler as f32)
+                    .zoom(self.scaler)
                     .shift(Point2f::from_floats(
                         (self.font_size.0 * x as i32) as f32,
                         (self.font_size.1 * y as i32) as f32,
                     ))
                     .into_iter()
                 {
-                    graphic_object.render(&mut self.canvas);
+                    graphic_object.render(canvas);
                 }
             }
         }
@@ -59,10 +57,10 @@ fn main() {
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
     let window_size: (u32, u32) = (540, 400);
-    let mut console = Console::new((18, 10));
+    let console = Console::new((30, 40));
 
     let window = video_subsystem
-        .window("fsdterm", window_size.0 as u32, window_size.1 as u32)
+        .window("fsdterm", window_size.0, window_size.1)
         .opengl()
         .position_centered()
         .build()
@@ -76,7 +74,7 @@ fn main() {
 
     let texture_creator = canvas.texture_creator();
     let mut texture = texture_creator
-        .create_texture_static(
+        .create_texture_streaming(
             Some(sdl2::pixels::PixelFormatEnum::RGB24),
             window_size.0,
             window_size.1,
@@ -85,10 +83,19 @@ fn main() {
 
     let mut event_pump = sdl_context.event_pump().unwrap();
 
-    console.render();
-
+    // drawn straight into the locked texture
     texture
-        .update(None, &console.canvas.data, window_size.0 as usize * 3)
+        .with_lock(None, |buffer, pitch| {
+            let mut canvas = Canvas::from_buffer(
+                buffer,
+                (window_size.0 as i32, window_size.1 as i32),
+                pitch,
+                PixelFormat::Rgb24,
+                1.,
+            );
+            canvas.set_gamma(Some(Gamma::srgb()));
+            console.render(&mut canvas);
+        })
         .unwrap();
 
     canvas.set_draw_color(Color::RGBA(0, 0, 0, 255));
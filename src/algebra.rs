@@ -210,6 +210,154 @@ impl Mat2x2f {
     }
 }
 
+// projective transform in homogeneous coordinates
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Mat3x3f {
+    // / x1 x2 x3 \
+    // | y1 y2 y3 |
+    // \ z1 z2 z3 /
+    pub x1: f32,
+    pub x2: f32,
+    pub x3: f32,
+    pub y1: f32,
+    pub y2: f32,
+    pub y3: f32,
+    pub z1: f32,
+    pub z2: f32,
+    pub z3: f32,
+}
+
+impl Mul<Point2f> for Mat3x3f {
+    type Output = Point2f;
+
+    // (x, y, 1) then divided by z
+    fn mul(self, rhs: Point2f) -> Point2f {
+        let z = self.z1 * rhs.x + self.z2 * rhs.y + self.z3;
+        Point2f {
+            x: (self.x1 * rhs.x + self.x2 * rhs.y + self.x3) / z,
+            y: (self.y1 * rhs.x + self.y2 * rhs.y + self.y3) / z,
+        }
+    }
+}
+
+impl Mul<Mat3x3f> for Mat3x3f {
+    type Output = Mat3x3f;
+
+    fn mul(self, rhs: Mat3x3f) -> Mat3x3f {
+        let row = |a: f32, b: f32, c: f32| {
+            (
+                a * rhs.x1 + b * rhs.y1 + c * rhs.z1,
+                a * rhs.x2 + b * rhs.y2 + c * rhs.z2,
+                a * rhs.x3 + b * rhs.y3 + c * rhs.z3,
+            )
+        };
+        let (x1, x2, x3) = row(self.x1, self.x2, self.x3);
+        let (y1, y2, y3) = row(self.y1, self.y2, self.y3);
+        let (z1, z2, z3) = row(self.z1, self.z2, self.z3);
+        Mat3x3f {
+            x1,
+            x2,
+            x3,
+            y1,
+            y2,
+            y3,
+            z1,
+            z2,
+            z3,
+        }
+    }
+}
+
+impl Mat3x3f {
+    pub fn identity() -> Mat3x3f {
+        Mat3x3f::from_affine(
+            Mat2x2f {
+                x1: 1.,
+                x2: 0.,
+                y1: 0.,
+                y2: 1.,
+            },
+            Point2f::new(),
+        )
+    }
+
+    // linear part then shift
+    pub fn from_affine(mat: Mat2x2f, shift: Point2f) -> Mat3x3f {
+        Mat3x3f {
+            x1: mat.x1,
+            x2: mat.x2,
+            x3: shift.x,
+            y1: mat.y1,
+            y2: mat.y2,
+            y3: shift.y,
+            z1: 0.,
+            z2: 0.,
+            z3: 1.,
+        }
+    }
+
+    // map (0, 0), (1, 0), (1, 1), (0, 1) to the quadrilateral
+    // None if three of the points are collinear
+    pub fn from_unit_square(quad: [Point2f; 4]) -> Option<Mat3x3f> {
+        let [p0, p1, p2, p3] = quad;
+        for i in 0..4 {
+            let a = quad[i];
+            if (quad[(i + 1) % 4] - a).crossx(quad[(i + 2) % 4] - a) == 0. {
+                return None;
+            }
+        }
+        let s = p0 - p1 + p2 - p3;
+        let d1 = p1 - p2;
+        let d2 = p3 - p2;
+        let den = d1.crossx(d2);
+        if den == 0. {
+            return None;
+        }
+        let g = s.crossx(d2) / den;
+        let h = d1.crossx(s) / den;
+        Some(Mat3x3f {
+            x1: p1.x - p0.x + g * p1.x,
+            x2: p3.x - p0.x + h * p3.x,
+            x3: p0.x,
+            y1: p1.y - p0.y + g * p1.y,
+            y2: p3.y - p0.y + h * p3.y,
+            y3: p0.y,
+            z1: g,
+            z2: h,
+            z3: 1.,
+        })
+    }
+
+    // four point correspondence, maps src[i] to dst[i]
+    pub fn from_quads(src: [Point2f; 4], dst: [Point2f; 4]) -> Option<Mat3x3f> {
+        Some(Mat3x3f::from_unit_square(dst)? * Mat3x3f::from_unit_square(src)?.inverse()?)
+    }
+
+    pub fn det(&self) -> f32 {
+        self.x1 * (self.y2 * self.z3 - self.y3 * self.z2)
+            - self.x2 * (self.y1 * self.z3 - self.y3 * self.z1)
+            + self.x3 * (self.y1 * self.z2 - self.y2 * self.z1)
+    }
+
+    pub fn inverse(&self) -> Option<Mat3x3f> {
+        let det = self.det();
+        if det == 0. {
+            return None;
+        }
+        Some(Mat3x3f {
+            x1: (self.y2 * self.z3 - self.y3 * self.z2) / det,
+            x2: (self.x3 * self.z2 - self.x2 * self.z3) / det,
+            x3: (self.x2 * self.y3 - self.x3 * self.y2) / det,
+            y1: (self.y3 * self.z1 - self.y1 * self.z3) / det,
+            y2: (self.x1 * self.z3 - self.x3 * self.z1) / det,
+            y3: (self.x3 * self.y1 - self.x1 * self.y3) / det,
+            z1: (self.y1 * self.z2 - self.y2 * self.z1) / det,
+            z2: (self.x2 * self.z1 - self.x1 * self.z2) / det,
+            z3: (self.x1 * self.y2 - self.x2 * self.y1) / det,
+        })
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct Rect2f {
     pub lu: Point2f,
@@ -259,7 +407,7 @@ impl Rect2f {
 
 #[cfg(test)]
 mod test {
-    use super::{linesegs_distance, Mat2x2f, Point2f};
+    use super::{linesegs_distance, Mat2x2f, Mat3x3f, Point2f};
 
     #[test]
     fn test_point2f_derive_more() {
@@ -294,6 +442,24 @@ mod test {
         assert!((point2f.y - 3.).abs() < eps);
     }
 
+    #[test]
+    fn test_mat3x3f() {
+        let eps: f32 = 1e-4;
+        let p = |x, y| Point2f::from_floats(x, y);
+        let src = [p(0., 0.), p(2., 0.), p(2., 1.), p(0., 1.)];
+        let dst = [p(1., 1.), p(5., 2.), p(4., 6.), p(0., 3.)];
+        let mat3x3f = Mat3x3f::from_quads(src, dst).unwrap();
+        for (s, d) in src.iter().zip(dst.iter()) {
+            assert!((mat3x3f * *s - *d).norm() < eps);
+        }
+        let inverse = mat3x3f.inverse().unwrap();
+        for (s, d) in src.iter().zip(dst.iter()) {
+            assert!((inverse * *d - *s).norm() < eps);
+        }
+        // collinear
+        assert!(Mat3x3f::from_unit_square([p(0., 0.), p(1., 1.), p(2., 2.), p(0., 1.)]).is_none());
+    }
+
     #[test]
     fn test_crossx() {
         let eps: f32 = 1e-6;
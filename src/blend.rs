@@ -0,0 +1,147 @@
+// how a drawn color is combined with what is already on the canvas
+// the alpha given to Canvas::putpixel, color alpha included, works as
+// coverage: the result below is mixed into the destination by it,
+// or replaces it for in and out, so pixels not drawn are never touched
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum BlendMode {
+    #[default]
+    SourceOver,
+    Multiply,
+    Screen,
+    Overlay,
+    Additive,
+    Darken,
+    Lighten,
+    Difference,
+    // Porter-Duff operators, the drawn color has alpha as its own
+    // and dest is only cleared under what is drawn, see dest_weight
+    In,
+    Out,
+    Atop,
+    Xor,
+    Clear,
+}
+
+impl BlendMode {
+    // source is straight rgb, dest and the result premultiplied rgba
+    pub fn apply(self, source: [f32; 3], dest: [f32; 4]) -> [f32; 4] {
+        let da = dest[3];
+        // separable modes mix the blended color in where dest is opaque
+        let separable = |f: fn(f32, f32) -> f32| {
+            let mut result = [0., 0., 0., 1.];
+            for (i, r) in result.iter_mut().take(3).enumerate() {
+                let d = if da > 0. { dest[i] / da } else { 0. };
+                *r = source[i] * (1. - da) + da * f(source[i], d);
+            }
+            result
+        };
+        // fa of the source, fb of the dest
+        let porter_duff = |fa: f32, fb: f32| {
+            let mut result = [0., 0., 0., fa + da * fb];
+            for (i, r) in result.iter_mut().take(3).enumerate() {
+                *r = source[i] * fa + dest[i] * fb;
+            }
+            result
+        };
+        match self {
+            BlendMode::SourceOver => [source[0], source[1], source[2], 1.],
+            BlendMode::Multiply => separable(|s, d| s * d),
+            BlendMode::Screen => separable(|s, d| s + d - s * d),
+            BlendMode::Overlay => separable(|s, d| {
+                if d <= 0.5 {
+                    2. * s * d
+                } else {
+                    1. - 2. * (1. - s) * (1. - d)
+                }
+            }),
+            // not clamped, accumulating canvases keep values above 1
+            BlendMode::Additive => [
+                source[0] + dest[0],
+                source[1] + dest[1],
+                source[2] + dest[2],
+                1.,
+            ],
+            BlendMode::Darken => separable(|s, d| s.min(d)),
+            BlendMode::Lighten => separable(|s, d| s.max(d)),
+            BlendMode::Difference => separable(|s, d| (s - d).abs()),
+            BlendMode::In | BlendMode::Atop => porter_duff(da, 0.),
+            BlendMode::Out | BlendMode::Xor => porter_duff(1. - da, 0.),
+            BlendMode::Clear => [0.; 4],
+        }
+    }
+
+    // how much of dest is left under a draw of alpha, in and out
+    // replace dest where drawn, the others mix their result in
+    // dest outside of what is drawn stays either way
+    pub fn dest_weight(self, alpha: f32) -> f32 {
+        match self {
+            BlendMode::In | BlendMode::Out => 0.,
+            _ => 1. - alpha,
+        }
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            BlendMode::SourceOver => "over",
+            BlendMode::Multiply => "multiply",
+            BlendMode::Screen => "screen",
+            BlendMode::Overlay => "overlay",
+            BlendMode::Additive => "additive",
+            BlendMode::Darken => "darken",
+            BlendMode::Lighten => "lighten",
+            BlendMode::Difference => "difference",
+            BlendMode::In => "in",
+            BlendMode::Out => "out",
+            BlendMode::Atop => "atop",
+            BlendMode::Xor => "xor",
+            BlendMode::Clear => "clear",
+        }
+    }
+
+    pub fn parse(string: &str) -> BlendMode {
+        match string {
+            "over" => BlendMode::SourceOver,
+            "multiply" => BlendMode::Multiply,
+            "screen" => BlendMode::Screen,
+            "overlay" => BlendMode::Overlay,
+            "additive" => BlendMode::Additive,
+            "darken" => BlendMode::Darken,
+            "lighten" => BlendMode::Lighten,
+            "difference" => BlendMode::Difference,
+            "in" => BlendMode::In,
+            "out" => BlendMode::Out,
+            "atop" => BlendMode::Atop,
+            "xor" => BlendMode::Xor,
+            "clear" => BlendMode::Clear,
+            _ => panic!("Format error"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BlendMode;
+
+    #[test]
+    fn test_blend_mode() {
+        let gray = [0.5, 0.5, 0.5, 1.];
+        let red = [1., 0., 0.];
+        assert_eq!(BlendMode::Multiply.apply(red, gray), [0.5, 0., 0., 1.]);
+        assert_eq!(BlendMode::Screen.apply(red, gray), [1., 0.5, 0.5, 1.]);
+        assert_eq!(BlendMode::Difference.apply(red, gray), [0.5, 0.5, 0.5, 1.]);
+        assert_eq!(BlendMode::Additive.apply(red, gray), [1.5, 0.5, 0.5, 1.]);
+        // nothing below, the source shows as it is
+        assert_eq!(BlendMode::Multiply.apply(red, [0.; 4]), [1., 0., 0., 1.]);
+        // half transparent white below
+        let dest = [0.5, 0.5, 0.5, 0.5];
+        assert_eq!(BlendMode::In.apply(red, dest), [0.5, 0., 0., 0.5]);
+        assert_eq!(BlendMode::Out.apply(red, dest), [0.5, 0., 0., 0.5]);
+        assert_eq!(BlendMode::Clear.apply(red, dest), [0.; 4]);
+        // half drawn, in drops the dest atop keeps
+        assert_eq!(BlendMode::In.dest_weight(0.5), 0.);
+        assert_eq!(BlendMode::Atop.dest_weight(0.5), 0.5);
+        for mode in [BlendMode::Overlay, BlendMode::Darken, BlendMode::Lighten].iter() {
+            assert_eq!(BlendMode::parse(mode.to_str()), *mode);
+        }
+    }
+}
@@ -1,45 +1,959 @@
-pub struct Canvas {
-    pub data: Vec<u8>,
+use std::ops::{Deref, DerefMut};
+
+use crate::algebra::{Mat2x2f, Mat3x3f, Point2f, Rect2f};
+use crate::blend::BlendMode;
+use crate::gamma::Gamma;
+use crate::mask::Mask;
+use crate::paint::Paint;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DownsampleFilter {
+    // average of the samples inside the pixel
+    Box,
+    // triangle weights reaching the centers of neighbor pixels
+    Tent,
+}
+
+// how values above 1, as left by additive blending on an accumulating
+// canvas, are brought into range on resolve
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ToneMap {
+    Clamp,
+    // v / (1 + v)
+    Reinhard,
+    // 1 - exp(-exposure * v)
+    Exposure(f32),
+}
+
+impl ToneMap {
+    pub fn apply(self, v: f32) -> f32 {
+        let v = v.max(0.);
+        match self {
+            ToneMap::Clamp => v.min(1.),
+            ToneMap::Reinhard => v / (1. + v),
+            ToneMap::Exposure(exposure) => 1. - (-exposure * v).exp(),
+        }
+    }
+}
+
+// 4x4 ordered dither thresholds, out of 16
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+// layout of one pixel in Canvas.data
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PixelFormat {
+    // r g b, drawn over black
+    Rgb24,
+    // b g r a, premultiplied, as little endian argb textures
+    Bgra32,
+    // r g b a, premultiplied
+    Rgba32,
+    // little endian 16 bits, red in the high 5
+    Rgb565,
+    // luma only, drawn over black
+    Gray8,
+    // coverage only, colors are dropped
+    Alpha8,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgb24 => 3,
+            PixelFormat::Bgra32 | PixelFormat::Rgba32 => 4,
+            PixelFormat::Rgb565 => 2,
+            PixelFormat::Gray8 | PixelFormat::Alpha8 => 1,
+        }
+    }
+
+    // formats without alpha are drawn over black
+    fn has_alpha(self) -> bool {
+        matches!(self, PixelFormat::Bgra32 | PixelFormat::Rgba32 | PixelFormat::Alpha8)
+    }
+
+    // premultiplied rgba in [0, 255]
+    fn read(self, bytes: &[u8]) -> [f32; 4] {
+        let byte = |i: usize| bytes[i] as f32;
+        match self {
+            PixelFormat::Rgb24 => [byte(0), byte(1), byte(2), 255.],
+            PixelFormat::Bgra32 => [byte(2), byte(1), byte(0), byte(3)],
+            PixelFormat::Rgba32 => [byte(0), byte(1), byte(2), byte(3)],
+            PixelFormat::Rgb565 => {
+                let v = u16::from_le_bytes([bytes[0], bytes[1]]);
+                [
+                    (v >> 11) as f32 * 255. / 31.,
+                    ((v >> 5) & 63) as f32 * 255. / 63.,
+                    (v & 31) as f32 * 255. / 31.,
+                    255.,
+                ]
+            }
+            PixelFormat::Gray8 => [byte(0), byte(0), byte(0), 255.],
+            PixelFormat::Alpha8 => [0., 0., 0., byte(0)],
+        }
+    }
+
+    // 8 bit channels are truncated, 565 ones rounded
+    // so reading and writing back does not drift
+    fn write(self, bytes: &mut [u8], pixel: [f32; 4]) {
+        match self {
+            PixelFormat::Rgb24 | PixelFormat::Rgba32 => {
+                let n = self.bytes_per_pixel();
+                for (b, c) in bytes[..n].iter_mut().zip(pixel.iter()) {
+                    *b = *c as u8;
+                }
+            }
+            PixelFormat::Bgra32 => {
+                for (b, i) in bytes[..4].iter_mut().zip([2, 1, 0, 3].iter()) {
+                    *b = pixel[*i] as u8;
+                }
+            }
+            PixelFormat::Rgb565 => {
+                let r = (pixel[0] * 31. / 255.).round() as u16;
+                let g = (pixel[1] * 63. / 255.).round() as u16;
+                let b = (pixel[2] * 31. / 255.).round() as u16;
+                bytes[..2].copy_from_slice(&(r << 11 | g << 5 | b).to_le_bytes());
+            }
+            // rec. 709 luma
+            PixelFormat::Gray8 => {
+                bytes[0] = (0.2126 * pixel[0] + 0.7152 * pixel[1] + 0.0722 * pixel[2]) as u8;
+            }
+            PixelFormat::Alpha8 => bytes[0] = pixel[3] as u8,
+        }
+    }
+}
+
+// owned pixels, or pixels of someone else like a locked texture
+pub enum PixelBuffer<'a> {
+    Owned(Vec<u8>),
+    Borrowed(&'a mut [u8]),
+}
+
+impl Deref for PixelBuffer<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            PixelBuffer::Owned(data) => data,
+            PixelBuffer::Borrowed(data) => data,
+        }
+    }
+}
+
+impl DerefMut for PixelBuffer<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match self {
+            PixelBuffer::Owned(data) => data,
+            PixelBuffer::Borrowed(data) => data,
+        }
+    }
+}
+
+pub struct Canvas<'a> {
+    // output pixels in format, written by resolve if there are samples
+    pub data: PixelBuffer<'a>,
+    // includes the supersampling factor
     pub scaler: f32,
+    // default for polygons without their own antialias setting
+    pub antialias: bool,
+    // width of wu lines in samples, one output pixel unless set
+    pub line_width: f32,
+    // size being rendered, supersampled if enabled
     size: (i32, i32),
+    // pixels drawn, x0 y0 x1 y1 with the ends excluded
+    clip: (i32, i32, i32, i32),
+    // clips to go back to by pop_clip
+    clip_stack: Vec<(i32, i32, i32, i32)>,
+    // intersection of the pushed masks
+    mask: Option<Mask>,
+    // masks to go back to by pop_mask
+    mask_stack: Vec<Option<Mask>>,
     color: [f32; 3],
+    // replaces color if set, with the map from pixel to paint space
+    paint: Option<(Paint, Mat3x3f)>,
+    blend_mode: BlendMode,
+    // blend in linear light if set, samples are linear then
+    gamma: Option<Gamma>,
+    format: PixelFormat,
+    // bytes from one row of data to the next
+    stride: usize,
+    // samples per pixel side, 1 renders straight into data
+    supersample: i32,
+    filter: DownsampleFilter,
+    // keep samples without supersampling, unclamped
+    accumulate: bool,
+    // premultiplied rgba in [0, 1] at the supersampled size,
+    // empty if neither supersampled nor accumulating
+    samples: Vec<f32>,
 }
 
-impl Canvas {
-    pub fn new(size: (i32, i32), scaler: f32) -> Canvas {
-        let size: (i32, i32) = (
+impl Canvas<'static> {
+    pub fn new(size: (i32, i32), scaler: f32) -> Canvas<'static> {
+        Canvas::with_format(size, scaler, PixelFormat::Rgb24, 1, DownsampleFilter::Box)
+    }
+
+    // transparent until drawn on
+    pub fn new_rgba(size: (i32, i32), scaler: f32) -> Canvas<'static> {
+        Canvas::with_format(size, scaler, PixelFormat::Rgba32, 1, DownsampleFilter::Box)
+    }
+
+    // render at factor x factor resolution, then filter down on finalize
+    pub fn new_supersampled(
+        size: (i32, i32),
+        scaler: f32,
+        factor: i32,
+        filter: DownsampleFilter,
+    ) -> Canvas<'static> {
+        Canvas::with_format(size, scaler, PixelFormat::Rgb24, factor, filter)
+    }
+
+    pub fn with_format(
+        size: (i32, i32),
+        scaler: f32,
+        format: PixelFormat,
+        factor: i32,
+        filter: DownsampleFilter,
+    ) -> Canvas<'static> {
+        let output_size: (i32, i32) = (
             ((size.0 as f32) * scaler) as i32,
             ((size.1 as f32) * scaler) as i32,
         );
-        Canvas {
-            data: vec![0; (size.0 * size.1 * 3) as usize],
+        let stride = output_size.0 as usize * format.bytes_per_pixel();
+        let data = vec![0; stride * output_size.1 as usize];
+        Canvas::build(
+            PixelBuffer::Owned(data),
+            output_size,
+            stride,
             scaler,
+            format,
+            factor,
+            filter,
+        )
+    }
+}
+
+impl<'a> Canvas<'a> {
+    // draw into pixels owned by someone else, size is in pixels
+    // rows are stride bytes apart, the buffer is not cleared
+    pub fn from_buffer(
+        buffer: &'a mut [u8],
+        size: (i32, i32),
+        stride: usize,
+        format: PixelFormat,
+        scaler: f32,
+    ) -> Canvas<'a> {
+        let row = size.0.max(0) as usize * format.bytes_per_pixel();
+        if stride < row
+            || (size.1 > 0 && buffer.len() < stride * (size.1 as usize - 1) + row)
+        {
+            panic!("buffer too small");
+        }
+        Canvas::build(
+            PixelBuffer::Borrowed(buffer),
+            size,
+            stride,
+            scaler,
+            format,
+            1,
+            DownsampleFilter::Box,
+        )
+    }
+
+    fn build(
+        data: PixelBuffer<'a>,
+        output_size: (i32, i32),
+        stride: usize,
+        scaler: f32,
+        format: PixelFormat,
+        factor: i32,
+        filter: DownsampleFilter,
+    ) -> Canvas<'a> {
+        let factor = factor.max(1);
+        let size = (output_size.0 * factor, output_size.1 * factor);
+        Canvas {
+            data,
+            scaler: scaler * factor as f32,
+            antialias: false,
+            line_width: factor as f32,
             size,
+            clip: (0, 0, size.0, size.1),
+            clip_stack: Vec::new(),
+            mask: None,
+            mask_stack: Vec::new(),
             color: [0., 0., 0.],
+            paint: None,
+            blend_mode: BlendMode::SourceOver,
+            gamma: None,
+            format,
+            stride,
+            supersample: factor,
+            filter,
+            accumulate: false,
+            samples: if factor > 1 {
+                vec![0.; (size.0 * size.1 * 4) as usize]
+            } else {
+                Vec::new()
+            },
         }
     }
 
     pub fn flush(&mut self) {
-        self.data = vec![0; (self.size.0 * self.size.1 * 3) as usize];
+        let row = self.get_output_size().0 as usize * self.format.bytes_per_pixel();
+        let stride = self.stride;
+        let height = self.get_output_size().1.max(0) as usize;
+        // a borrowed buffer may go on past the canvas
+        for line in self.data.chunks_mut(stride).take(height) {
+            for x in line.iter_mut().take(row) {
+                *x = 0;
+            }
+        }
+        for x in self.samples.iter_mut() {
+            *x = 0.;
+        }
+    }
+
+    pub fn get_size(&self) -> (i32, i32) {
+        self.size
+    }
+
+    // size of data
+    pub fn get_output_size(&self) -> (i32, i32) {
+        (self.size.0 / self.supersample, self.size.1 / self.supersample)
+    }
+
+    // samples per output pixel side
+    pub fn get_supersample(&self) -> i32 {
+        self.supersample
+    }
+
+    pub fn get_format(&self) -> PixelFormat {
+        self.format
     }
 
+    pub fn get_stride(&self) -> usize {
+        self.stride
+    }
+
+    // bytes of output pixel (x, y) in data
+    fn pixel_range(&self, x: i32, y: i32) -> std::ops::Range<usize> {
+        let bpp = self.format.bytes_per_pixel();
+        let pos = y as usize * self.stride + x as usize * bpp;
+        pos..pos + bpp
+    }
+
+    // every output pixel as premultiplied rgba in [0, 255], row by row
+    fn read_pixels(&self) -> Vec<[f32; 4]> {
+        let (width, height) = self.get_output_size();
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                pixels.push(self.format.read(&self.data[self.pixel_range(x, y)]));
+            }
+        }
+        pixels
+    }
+
+    // data as premultiplied rgba8, opaque for formats without alpha
+    // supersampled or accumulating canvases show what the last
+    // resolve or finalize left in data, not the draws since
+    pub fn get_premultiplied(&self) -> Vec<u8> {
+        let pixels = self.read_pixels();
+        let mut bytes = Vec::with_capacity(pixels.len() * 4);
+        for pixel in pixels.iter() {
+            bytes.extend(pixel.iter().map(|c| c.round() as u8));
+        }
+        bytes
+    }
+
+    // data as straight rgba8, for consumers not aware of premultiplication
+    // stale the same way as get_premultiplied until resolved
+    pub fn get_straight(&self) -> Vec<u8> {
+        let pixels = self.read_pixels();
+        let mut bytes = Vec::with_capacity(pixels.len() * 4);
+        for pixel in pixels.iter() {
+            let alpha = pixel[3];
+            let mut straight = [0u8; 4];
+            if alpha > 0. {
+                for (s, c) in straight.iter_mut().zip(pixel.iter()).take(3) {
+                    *s = (c * 255. / alpha).round().min(255.) as u8;
+                }
+            }
+            straight[3] = alpha as u8;
+            bytes.extend_from_slice(&straight);
+        }
+        bytes
+    }
+
+    // pixel space rectangle worth rasterizing, geometry out of it
+    // can be clipped away before drawing
+    pub fn visible_rect(&self, margin: f32) -> Rect2f {
+        Rect2f::from_floats(
+            self.clip.0 as f32 - margin,
+            self.clip.1 as f32 - margin,
+            self.clip.2 as f32 + margin,
+            self.clip.3 as f32 + margin,
+        )
+    }
+
+    // only draw pixels whose centers are inside rect until pop_clip,
+    // rect is in the same coordinates as graphic objects
+    // and is intersected with the current clip
+    pub fn push_clip(&mut self, rect: Rect2f) {
+        let to_pixel = |v: f32| (v * self.scaler - 0.5).ceil() as i32;
+        let (x0, y0) = (to_pixel(rect.lu.x), to_pixel(rect.lu.y));
+        let (x1, y1) = (to_pixel(rect.rd.x), to_pixel(rect.rd.y));
+        let clip = self.clip;
+        self.clip_stack.push(clip);
+        let x0 = x0.max(clip.0);
+        let y0 = y0.max(clip.1);
+        // empty clips keep their ends in order
+        self.clip = (x0, y0, x1.min(clip.2).max(x0), y1.min(clip.3).max(y0));
+    }
+
+    pub fn pop_clip(&mut self) {
+        self.clip = self.clip_stack.pop().expect("clip stack empty");
+    }
+
+    // pixels drawn as x0 y0 x1 y1, the ends excluded
+    pub fn get_clip(&self) -> (i32, i32, i32, i32) {
+        self.clip
+    }
+
+    // alpha of draws is multiplied by mask until pop_mask,
+    // intersected with the current mask
+    pub fn push_mask(&mut self, mask: &Mask) {
+        assert_eq!(mask.get_size(), self.size, "mask size mismatch");
+        let intersected = match &self.mask {
+            Some(current) => current.intersect(mask),
+            None => mask.clone(),
+        };
+        let current = self.mask.replace(intersected);
+        self.mask_stack.push(current);
+    }
+
+    pub fn pop_mask(&mut self) {
+        self.mask = self.mask_stack.pop().expect("mask stack empty");
+    }
+
+    pub fn get_mask(&self) -> Option<&Mask> {
+        self.mask.as_ref()
+    }
+
+    // also drops the paint
     pub fn set_color(&mut self, color: [f32; 3]) {
         self.color = color;
+        self.paint = None;
+    }
+
+    // used by putpixel until changed
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
+
+    pub fn get_blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    // colors and data are taken as encoded by gamma, and blended
+    // after decoding, so antialiased edges keep their weight
+    // should be set before drawing, samples are not converted
+    pub fn set_gamma(&mut self, gamma: Option<Gamma>) {
+        self.gamma = gamma;
+    }
+
+    pub fn get_gamma(&self) -> Option<&Gamma> {
+        self.gamma.as_ref()
+    }
+
+    // draw into f32 samples at full precision and without clamping,
+    // data and so the readbacks are only updated by resolve, in one step
+    // samples start from data, turning it off drops what is not resolved
+    pub fn set_accumulate(&mut self, accumulate: bool) {
+        self.accumulate = accumulate;
+        if self.supersample > 1 {
+            return;
+        }
+        self.samples = if accumulate {
+            self.read_pixels()
+                .iter()
+                .flat_map(|pixel| match &self.gamma {
+                    Some(gamma) => gamma.pixel_to_linear(*pixel),
+                    None => pixel.map(|c| c / 255.),
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+    }
+
+    pub fn get_accumulate(&self) -> bool {
+        self.accumulate
+    }
+
+    // evaluated at every pixel center until the next set_color,
+    // the paint alpha multiplies the alpha given to putpixel
+    // a degenerate paint transform leaves the solid color
+    pub fn set_paint(&mut self, paint: &Paint) {
+        let to_object = Mat3x3f::from_affine(
+            Mat2x2f {
+                x1: 1. / self.scaler,
+                x2: 0.,
+                y1: 0.,
+                y2: 1. / self.scaler,
+            },
+            Point2f::new(),
+        );
+        self.paint = paint
+            .transform
+            .inverse()
+            .map(|inverse| (paint.clone(), inverse * to_object));
     }
 
     #[inline]
     pub fn putpixel(&mut self, x: i32, y: i32, alpha: f32) {
-        if x < 0 || y < 0 || x >= self.size.0 || y >= self.size.1 {
+        let clip = self.clip;
+        if x < clip.0 || y < clip.1 || x >= clip.2 || y >= clip.3 || alpha <= 0. {
+            return;
+        }
+        self.blend_pixel(x, y, alpha);
+    }
+
+    // putpixel past the clip test, (x, y) must be inside the clip
+    // and alpha positive
+    #[inline]
+    fn blend_pixel(&mut self, x: i32, y: i32, alpha: f32) {
+        let alpha = match &self.mask {
+            Some(mask) => alpha * mask.coverage(x, y),
+            None => alpha,
+        };
+        if alpha <= 0. {
+            return;
+        }
+        let (color, alpha) = match &self.paint {
+            None => (self.color, alpha),
+            Some((paint, to_paint)) => {
+                let center = Point2f::from_floats(x as f32 + 0.5, y as f32 + 0.5);
+                let rgba = paint.color_at(*to_paint * center);
+                ([rgba[0], rgba[1], rgba[2]], alpha * rgba[3])
+            }
+        };
+        let color = match &self.gamma {
+            Some(gamma) => gamma.decode_color(color),
+            None => color,
+        };
+        // the blended color is mixed in by alpha, premultiplied
+        let index = (y * self.size.0 + x) as usize;
+        if !self.samples.is_empty() {
+            let pos = index * 4;
+            let dest = [
+                self.samples[pos],
+                self.samples[pos + 1],
+                self.samples[pos + 2],
+                self.samples[pos + 3],
+            ];
+            let mut source = self.blend_mode.apply(color, dest);
+            if !self.accumulate {
+                source = source.map(|c| c.min(1.));
+            }
+            let weight = self.blend_mode.dest_weight(alpha);
+            for (s, c) in self.samples[pos..pos + 4].iter_mut().zip(source.iter()) {
+                *s = *s * weight + c * alpha;
+            }
             return;
         }
-        let mut pos = ((y * self.size.0 + x) * 3) as usize;
-        self.data[pos] =
-            (self.data[pos] as f32 * (1. - alpha) + self.color[0] * 255. * alpha) as u8;
-        pos += 1;
-        self.data[pos] =
-            (self.data[pos] as f32 * (1. - alpha) + self.color[1] * 255. * alpha) as u8;
-        pos += 1;
-        self.data[pos] =
-            (self.data[pos] as f32 * (1. - alpha) + self.color[2] * 255. * alpha) as u8;
+        let range = self.pixel_range(x, y);
+        let mut pixel = self.format.read(&self.data[range.clone()]);
+        if let Some(gamma) = &self.gamma {
+            let mut dest = gamma.pixel_to_linear(pixel);
+            let source = self.blend_mode.apply(color, dest).map(|c| c.min(1.));
+            let weight = self.blend_mode.dest_weight(alpha);
+            for (d, c) in dest.iter_mut().zip(source.iter()) {
+                *d = *d * weight + c * alpha;
+            }
+            let mut pixel = gamma.linear_to_pixel(dest);
+            for p in pixel.iter_mut() {
+                *p = p.round();
+            }
+            self.format.write(&mut self.data[range], pixel);
+            return;
+        }
+        let source = match self.blend_mode {
+            BlendMode::SourceOver => [color[0], color[1], color[2], 1.],
+            blend_mode => {
+                let mut dest = pixel;
+                for d in dest.iter_mut() {
+                    *d /= 255.;
+                }
+                blend_mode.apply(color, dest)
+            }
+        };
+        let weight = self.blend_mode.dest_weight(alpha);
+        for (d, c) in pixel.iter_mut().zip(source.iter()) {
+            *d = *d * weight + c.min(1.) * 255. * alpha;
+        }
+        self.format.write(&mut self.data[range], pixel);
+    }
+
+    // pixels x0..x1 on row y, clipped as a whole
+    pub fn fill_span(&mut self, y: i32, x0: i32, x1: i32, alpha: f32) {
+        let clip = self.clip;
+        if y < clip.1 || y >= clip.3 || alpha <= 0. {
+            return;
+        }
+        for x in x0.max(clip.0)..x1.min(clip.2) {
+            self.blend_pixel(x, y, alpha);
+        }
+    }
+
+    // weights of samples for output pixel 0 along one axis,
+    // as (sample offset, weight), other pixels shift by factor
+    fn filter_taps(&self) -> Vec<(i32, f32)> {
+        let factor = self.supersample;
+        let mut taps: Vec<(i32, f32)> = match self.filter {
+            DownsampleFilter::Box => (0..factor).map(|i| (i, 1.)).collect(),
+            DownsampleFilter::Tent => (-factor..2 * factor)
+                .map(|i| {
+                    // distance from the pixel center in output pixels
+                    let d = ((i as f32 + 0.5) / factor as f32 - 0.5).abs();
+                    (i, 1. - d)
+                })
+                .filter(|x| x.1 > 0.)
+                .collect(),
+        };
+        let sum: f32 = taps.iter().map(|x| x.1).sum();
+        for tap in taps.iter_mut() {
+            tap.1 /= sum;
+        }
+        taps
+    }
+
+    // resolve with values above 1 clamped and no dithering
+    pub fn finalize(&mut self) {
+        self.resolve(ToneMap::Clamp, false);
+    }
+
+    // filter the samples down into data, nothing to do without them
+    // tone mapping works on straight colors, linear if gamma is set,
+    // dithering spreads the rounding of encoded values over 4x4 pixels
+    pub fn resolve(&mut self, tone_map: ToneMap, dither: bool) {
+        if self.samples.is_empty() {
+            return;
+        }
+        let factor = self.supersample;
+        let (width, height) = self.size;
+        let output_size = self.get_output_size();
+        let taps = self.filter_taps();
+        let has_alpha = self.format.has_alpha();
+        // separable, horizontal pass first
+        let mut horizontal = vec![0f32; (output_size.0 * height * 4) as usize];
+        for y in 0..height {
+            for x in 0..output_size.0 {
+                let mut sum = [0f32; 4];
+                let mut weight = 0.;
+                for (offset, w) in taps.iter() {
+                    let sx = x * factor + offset;
+                    if sx < 0 || sx >= width {
+                        continue;
+                    }
+                    let pos = ((y * width + sx) * 4) as usize;
+                    for (c, s) in sum.iter_mut().enumerate() {
+                        *s += self.samples[pos + c] * w;
+                    }
+                    weight += w;
+                }
+                let pos = ((y * output_size.0 + x) * 4) as usize;
+                for (c, s) in sum.iter().enumerate() {
+                    horizontal[pos + c] = s / weight;
+                }
+            }
+        }
+        for y in 0..output_size.1 {
+            for x in 0..output_size.0 {
+                let mut sum = [0f32; 4];
+                let mut weight = 0.;
+                for (offset, w) in taps.iter() {
+                    let sy = y * factor + offset;
+                    if sy < 0 || sy >= height {
+                        continue;
+                    }
+                    let pos = ((sy * output_size.0 + x) * 4) as usize;
+                    for (c, s) in sum.iter_mut().enumerate() {
+                        *s += horizontal[pos + c] * w;
+                    }
+                    weight += w;
+                }
+                let mut pixel = [0f32; 4];
+                for (p, s) in pixel.iter_mut().zip(sum.iter()) {
+                    *p = s / weight;
+                }
+                // over black is the premultiplied color made opaque
+                if !has_alpha {
+                    pixel[3] = 1.;
+                }
+                let alpha = pixel[3].clamp(0., 1.);
+                pixel[3] = alpha;
+                if alpha > 0. {
+                    for c in pixel.iter_mut().take(3) {
+                        *c = tone_map.apply(*c / alpha) * alpha;
+                    }
+                }
+                let mut pixel = match &self.gamma {
+                    Some(gamma) => gamma.linear_to_pixel(pixel),
+                    None => pixel.map(|p| p * 255.),
+                };
+                let offset = if dither {
+                    (BAYER[(y & 3) as usize][(x & 3) as usize] as f32 + 0.5) / 16. - 0.5
+                } else {
+                    0.
+                };
+                let alpha = pixel[3].round();
+                for c in pixel.iter_mut().take(3) {
+                    // premultiplied colors stay under alpha
+                    *c = (*c + offset).round().clamp(0., alpha);
+                }
+                pixel[3] = alpha;
+                let range = self.pixel_range(x, y);
+                self.format.write(&mut self.data[range], pixel);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Canvas, DownsampleFilter, PixelFormat, ToneMap};
+    use crate::algebra::{Point2f, Rect2f};
+    use crate::blend::BlendMode;
+    use crate::gamma::Gamma;
+    use crate::graphic_object::{GraphicObject, Polygon2f};
+
+    #[test]
+    fn test_supersample() {
+        // covers the left half of pixel (1, 0)
+        let polygon2f = Polygon2f::new(
+            vec![
+                Point2f::from_floats(0., 0.),
+                Point2f::from_floats(1.5, 0.),
+                Point2f::from_floats(1.5, 1.),
+                Point2f::from_floats(0., 1.),
+            ],
+            [1., 1., 1., 1.],
+            [0., 0., 0., 0.],
+        );
+        let mut canvas = Canvas::new_supersampled((3, 1), 1., 4, DownsampleFilter::Box);
+        assert_eq!(canvas.get_size(), (12, 4));
+        polygon2f.render(&mut canvas);
+        canvas.finalize();
+        assert_eq!(canvas.data[0], 255);
+        assert_eq!(canvas.data[3], 128);
+        assert_eq!(canvas.data[6], 0);
+
+        // the tent reaches the next pixel, the box does not
+        let polygon2f = polygon2f.zoom(1. / 1.5);
+        for (filter, expect_neighbor) in [
+            (DownsampleFilter::Box, false),
+            (DownsampleFilter::Tent, true),
+        ]
+        .iter()
+        {
+            let mut canvas = Canvas::new_supersampled((3, 1), 1., 4, *filter);
+            polygon2f.render(&mut canvas);
+            canvas.finalize();
+            assert_eq!(canvas.data[3] > 0, *expect_neighbor);
+            assert_eq!(canvas.data[6], 0);
+        }
+    }
+
+    #[test]
+    fn test_rgba() {
+        let mut canvas = Canvas::new_rgba((2, 1), 1.);
+        assert_eq!(*canvas.data, [0; 8]);
+        // half red, then half green over the first pixel only
+        canvas.set_color([1., 0., 0.]);
+        canvas.fill_span(0, 0, 2, 0.5);
+        canvas.set_color([0., 1., 0.]);
+        canvas.putpixel(0, 0, 0.5);
+        assert_eq!(canvas.get_premultiplied(), vec![63, 127, 0, 191, 127, 0, 0, 127]);
+        assert_eq!(canvas.get_straight(), vec![84, 170, 0, 191, 255, 0, 0, 127]);
+
+        // the background stays transparent through supersampling
+        let mut canvas =
+            Canvas::with_format((2, 1), 1., PixelFormat::Rgba32, 2, DownsampleFilter::Box);
+        canvas.set_color([1., 1., 1.]);
+        canvas.fill_span(0, 0, 2, 1.);
+        canvas.finalize();
+        assert_eq!(canvas.get_straight(), vec![255, 255, 255, 128, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_formats() {
+        // 2x2 bgra with 2 bytes of padding after every row,
+        // and a row more than the canvas
+        let mut buffer = vec![0xaa; 30];
+        {
+            let mut canvas = Canvas::from_buffer(&mut buffer, (2, 2), 10, PixelFormat::Bgra32, 1.);
+            canvas.flush();
+            canvas.set_color([1., 0.5, 0.]);
+            canvas.putpixel(1, 1, 1.);
+            assert_eq!(canvas.get_straight()[12..], [255, 127, 0, 255]);
+        }
+        assert_eq!(buffer[10..20], [0, 0, 0, 0, 0, 127, 255, 255, 0xaa, 0xaa]);
+        assert_eq!(buffer[8..10], [0xaa, 0xaa]);
+        assert_eq!(buffer[20..], [0xaa; 10]);
+
+        let draw = |format: PixelFormat, alpha: f32| {
+            let mut canvas = Canvas::with_format((1, 1), 1., format, 1, DownsampleFilter::Box);
+            canvas.set_color([1., 0.5, 0.]);
+            canvas.putpixel(0, 0, alpha);
+            // nothing drawn does not change the pixel
+            canvas.putpixel(0, 0, 0.);
+            canvas.data.to_vec()
+        };
+        // 31, 32 and 0 of 31, 63 and 31
+        assert_eq!(draw(PixelFormat::Rgb565, 1.), vec![0x00, 0xfc]);
+        assert_eq!(draw(PixelFormat::Gray8, 1.), vec![145]);
+        assert_eq!(draw(PixelFormat::Alpha8, 0.5), vec![127]);
+    }
+
+    #[test]
+    fn test_gamma() {
+        // half covered white over black is half the light, not half the byte
+        let mut canvas = Canvas::new((2, 1), 1.);
+        canvas.set_gamma(Some(Gamma::srgb()));
+        canvas.set_color([1., 1., 1.]);
+        canvas.putpixel(0, 0, 0.5);
+        canvas.set_color([0.5, 0.5, 0.5]);
+        canvas.putpixel(1, 0, 1.);
+        // opaque colors come out as the nearest byte
+        assert_eq!(*canvas.data, [188, 188, 188, 128, 128, 128]);
+
+        // samples are linear, filtered down before encoding
+        let mut canvas = Canvas::new_supersampled((1, 1), 1., 2, DownsampleFilter::Box);
+        canvas.set_gamma(Some(Gamma::srgb()));
+        canvas.set_color([1., 1., 1.]);
+        canvas.fill_span(0, 0, 2, 1.);
+        canvas.finalize();
+        assert_eq!(*canvas.data, [188, 188, 188]);
+    }
+
+    #[test]
+    fn test_accumulate() {
+        // many faint draws reach the color, truncating to bytes stalls
+        let mut canvas = Canvas::new((2, 1), 1.);
+        canvas.set_color([0.5, 0.5, 0.5]);
+        for _ in 0..100 {
+            canvas.putpixel(0, 0, 0.05);
+        }
+        canvas.set_accumulate(true);
+        for _ in 0..100 {
+            canvas.putpixel(1, 0, 0.05);
+        }
+        // data is untouched until resolved, readbacks too
+        assert_eq!(canvas.data[3], 0);
+        assert_eq!(canvas.get_premultiplied()[4..], [0, 0, 0, 255]);
+        canvas.finalize();
+        // stuck where a 0.05 step rounds to nothing
+        assert_eq!(canvas.data[0], 108);
+        assert_eq!(canvas.data[3], 127);
+
+        // additive glow above 1, brought back by the tone map
+        let mut canvas = Canvas::new((1, 1), 1.);
+        canvas.set_accumulate(true);
+        canvas.set_blend_mode(BlendMode::Additive);
+        canvas.set_color([0.6, 0.6, 0.6]);
+        canvas.putpixel(0, 0, 1.);
+        canvas.putpixel(0, 0, 1.);
+        canvas.finalize();
+        assert_eq!(canvas.data[0], 255);
+        canvas.resolve(ToneMap::Reinhard, false);
+        assert_eq!(canvas.data[0], 139);
+
+        // in between two bytes, dithered half to each
+        let mut canvas = Canvas::new((4, 4), 1.);
+        canvas.set_accumulate(true);
+        canvas.set_color([100.5 / 255., 0., 0.]);
+        for y in 0..4 {
+            canvas.fill_span(y, 0, 4, 1.);
+        }
+        canvas.resolve(ToneMap::Clamp, true);
+        let reds: Vec<u8> = canvas.data.iter().step_by(3).cloned().collect();
+        assert_eq!(reds.iter().filter(|x| **x == 100).count(), 8);
+        assert_eq!(reds.iter().filter(|x| **x == 101).count(), 8);
+    }
+
+    #[test]
+    fn test_clip() {
+        let square = |k: f32| {
+            Polygon2f::new(
+                vec![
+                    Point2f::from_floats(-k, -k),
+                    Point2f::from_floats(k, -k),
+                    Point2f::from_floats(k, k),
+                    Point2f::from_floats(-k, k),
+                ],
+                [1., 1., 1., 1.],
+                [0., 0., 0., 0.],
+            )
+        };
+        for antialias in [false, true].iter() {
+            let mut canvas = Canvas::new((2, 2), 2.);
+            canvas.antialias = *antialias;
+            // nested clips intersect, in object coordinates
+            canvas.push_clip(Rect2f::from_floats(0., 0., 1.5, 1.5));
+            canvas.push_clip(Rect2f::from_floats(0.5, 0.5, 5., 5.));
+            assert_eq!(canvas.get_clip(), (1, 1, 3, 3));
+            square(10.).render(&mut canvas);
+            canvas.pop_clip();
+            assert_eq!(canvas.get_clip(), (0, 0, 3, 3));
+            canvas.pop_clip();
+            assert_eq!(canvas.get_clip(), (0, 0, 4, 4));
+            for y in 0..4 {
+                for x in 0..4 {
+                    let inside = (1..3).contains(&x) && (1..3).contains(&y);
+                    let expected = if inside { 255 } else { 0 };
+                    assert_eq!(canvas.data[(y * 4 + x) * 3], expected, "{} {}", x, y);
+                }
+            }
+        }
+
+        // antialiased edges crossing the clip bounds within row 1,
+        // x = y + 0.5 and x = 6.5 - 0.375y, pixels inside are as without
+        // the clip but for the last bit of float sums writes truncate
+        let slanted = Polygon2f::new(
+            vec![
+                Point2f::from_floats(0.5, 0.),
+                Point2f::from_floats(6.5, 0.),
+                Point2f::from_floats(5., 4.),
+                Point2f::from_floats(4.5, 4.),
+            ],
+            [1., 1., 1., 1.],
+            [0., 0., 0., 0.],
+        );
+        let mut whole = Canvas::new((8, 4), 1.);
+        whole.antialias = true;
+        slanted.render(&mut whole);
+        let mut clipped = Canvas::new((8, 4), 1.);
+        clipped.antialias = true;
+        clipped.push_clip(Rect2f::from_floats(2., 1., 6., 3.));
+        slanted.render(&mut clipped);
+        for y in 0..4 {
+            for x in 0..8 {
+                let i = (y * 8 + x) * 3;
+                let inside = (2..6).contains(&x) && (1..3).contains(&y);
+                let expected = if inside { whole.data[i] as i32 } else { 0 };
+                let error = (clipped.data[i] as i32 - expected).abs();
+                assert!(error <= 1, "{} {} {} {}", x, y, clipped.data[i], expected);
+            }
+        }
+
+        // disjoint clips leave nothing to draw
+        let mut canvas = Canvas::new((4, 4), 1.);
+        canvas.push_clip(Rect2f::from_floats(0., 0., 1., 1.));
+        canvas.push_clip(Rect2f::from_floats(2., 2., 4., 4.));
+        let rect = canvas.visible_rect(0.);
+        assert_eq!(rect.get_size().x, 0.);
+        canvas.set_color([1., 1., 1.]);
+        canvas.fill_span(2, 0, 4, 1.);
+        canvas.putpixel(3, 3, 1.);
+        assert!(canvas.data.iter().all(|x| *x == 0));
     }
 }
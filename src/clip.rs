@@ -0,0 +1,760 @@
+use std::collections::HashMap;
+
+use crate::algebra::{Point2f, Rect2f};
+use crate::rasterizer::FillRule;
+
+// relative tolerance for intersections landing on vertices
+const DEGENERATE_EPS: f32 = 1e-6;
+
+// intersection of segment ab and cd(including end point)
+// returns the parameters on ab and cd
+pub fn segment_intersection(a: Point2f, b: Point2f, c: Point2f, d: Point2f) -> Option<(f32, f32)> {
+    let r = b - a;
+    let s = d - c;
+    let denom = r.crossx(s);
+    if denom == 0. {
+        return None;
+    }
+    let ac = c - a;
+    let t = ac.crossx(s) / denom;
+    let u = ac.crossx(r) / denom;
+    if (0. ..=1.).contains(&t) && (0. ..=1.).contains(&u) {
+        Some((t, u))
+    } else {
+        None
+    }
+}
+
+// positive for counterclockwise in y-up coordinates
+pub fn signed_area(vertices: &[Point2f]) -> f32 {
+    let mut area = 0.;
+    let mut last = match vertices.last() {
+        Some(last) => *last,
+        None => return 0.,
+    };
+    for vertex in vertices.iter() {
+        area += last.crossx(*vertex);
+        last = *vertex;
+    }
+    area / 2.
+}
+
+pub fn is_convex(vertices: &[Point2f]) -> bool {
+    let len = vertices.len();
+    if len < 3 {
+        return false;
+    }
+    let mut sign = 0.;
+    for i in 0..len {
+        let a = vertices[i];
+        let b = vertices[(i + 1) % len];
+        let c = vertices[(i + 2) % len];
+        let cross = (b - a).crossx(c - b);
+        if cross != 0. {
+            if sign * cross < 0. {
+                return false;
+            }
+            sign = cross;
+        }
+    }
+    sign != 0.
+}
+
+// even-odd test, points on the border are undefined
+pub fn point_in_polygon(point: Point2f, vertices: &[Point2f]) -> bool {
+    let mut inside = false;
+    let mut last = match vertices.last() {
+        Some(last) => *last,
+        None => return false,
+    };
+    for vertex in vertices.iter() {
+        if (vertex.y > point.y) != (last.y > point.y) {
+            let x = vertex.x + (point.y - vertex.y) * (last.x - vertex.x) / (last.y - vertex.y);
+            if point.x < x {
+                inside = !inside;
+            }
+        }
+        last = *vertex;
+    }
+    inside
+}
+
+// +1 for every downward crossing of the ray to the right, -1 for upward
+pub fn winding_number(point: Point2f, vertices: &[Point2f]) -> i32 {
+    let mut winding = 0;
+    let mut last = match vertices.last() {
+        Some(last) => *last,
+        None => return 0,
+    };
+    for vertex in vertices.iter() {
+        if (vertex.y > point.y) != (last.y > point.y) {
+            let x = vertex.x + (point.y - vertex.y) * (last.x - vertex.x) / (last.y - vertex.y);
+            if point.x < x {
+                winding += if vertex.y > last.y { 1 } else { -1 };
+            }
+        }
+        last = *vertex;
+    }
+    winding
+}
+
+// inside test for multiple contours filled together
+pub fn contains(point: Point2f, contours: &[Vec<Point2f>], fill_rule: FillRule) -> bool {
+    fill_rule.is_inside(
+        contours
+            .iter()
+            .map(|contour| winding_number(point, contour))
+            .sum(),
+    )
+}
+
+// keep the part of the ring on the left side of ab(cross >= 0)
+pub fn clip_half_plane(vertices: &[Point2f], a: Point2f, b: Point2f) -> Vec<Point2f> {
+    let mut result = Vec::new();
+    let ab = b - a;
+    let mut last = match vertices.last() {
+        Some(last) => *last,
+        None => return result,
+    };
+    let mut last_side = ab.crossx(last - a);
+    for vertex in vertices.iter() {
+        let side = ab.crossx(*vertex - a);
+        if (side >= 0.) != (last_side >= 0.) {
+            let t = last_side / (last_side - side);
+            result.push(last + (*vertex - last) * t);
+        }
+        if side >= 0. {
+            result.push(*vertex);
+        }
+        last = *vertex;
+        last_side = side;
+    }
+    result
+}
+
+// the part of a simple ring on the left side of ab, as separate rings
+// clip_half_plane alone joins the pieces of a concave ring
+// with zero width edges along the line
+pub fn split_half_plane(vertices: &[Point2f], a: Point2f, b: Point2f) -> Vec<Vec<Point2f>> {
+    let ring = clip_half_plane(vertices, a, b);
+    let len = ring.len();
+    if len < 3 {
+        return Vec::new();
+    }
+    let ab = b - a;
+    let size = ring.iter().fold(0f32, |max, p| max.max((*p - a).norm()));
+    let on_line = |p: Point2f| ab.crossx(p - a).abs() <= DEGENERATE_EPS * size * ab.norm();
+    let edge_on_line = |i: usize| on_line(ring[i]) && on_line(ring[(i + 1) % len]);
+    if (0..len).all(edge_on_line) {
+        return Vec::new();
+    }
+    // runs of edges along the line, from where the ring arrives on it
+    // to where it leaves, as (t along ab, index, arrives)
+    let mut ends: Vec<(f32, usize, bool)> = Vec::new();
+    for i in 0..len {
+        if edge_on_line(i) && !edge_on_line((i + len - 1) % len) {
+            let mut j = i;
+            while edge_on_line(j) {
+                j = (j + 1) % len;
+            }
+            ends.push(((ring[i] - a).dotx(ab), i, true));
+            ends.push(((ring[j] - a).dotx(ab), j, false));
+        }
+    }
+    if ends.len() <= 2 {
+        return vec![ring];
+    }
+    // inside the polygon the line is covered by the sorted ends in pairs,
+    // every pair links an arrival to a leave
+    ends.sort_by(|x, y| x.0.total_cmp(&y.0));
+    let mut jump: Vec<Option<usize>> = vec![None; len];
+    for pair in ends.chunks(2) {
+        match pair {
+            [(_, i, true), (_, j, false)] | [(_, j, false), (_, i, true)] => jump[*i] = Some(*j),
+            _ => return vec![ring],
+        }
+    }
+    let mut visited = vec![false; len];
+    // vertices in the middle of a run are dropped
+    for (i, v) in visited.iter_mut().enumerate() {
+        if edge_on_line(i) && edge_on_line((i + len - 1) % len) {
+            *v = true;
+        }
+    }
+    let mut result = Vec::new();
+    for start in 0..len {
+        if visited[start] {
+            continue;
+        }
+        let mut piece = Vec::new();
+        let mut i = start;
+        while !visited[i] {
+            visited[i] = true;
+            piece.push(ring[i]);
+            i = jump[i].unwrap_or((i + 1) % len);
+        }
+        if piece.len() >= 3 {
+            result.push(piece);
+        }
+    }
+    result
+}
+
+// Liang-Barsky, the part of segment ab inside the rectangle
+pub fn clip_segment_rect(a: Point2f, b: Point2f, rect: Rect2f) -> Option<(Point2f, Point2f)> {
+    let d = b - a;
+    let mut t0: f32 = 0.;
+    let mut t1: f32 = 1.;
+    for (p, q) in [
+        (-d.x, a.x - rect.lu.x),
+        (d.x, rect.rd.x - a.x),
+        (-d.y, a.y - rect.lu.y),
+        (d.y, rect.rd.y - a.y),
+    ]
+    .iter()
+    {
+        if *p == 0. {
+            // parallel and outside
+            if *q < 0. {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if *p < 0. {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    Some((a + d * t0, a + d * t1))
+}
+
+// Sutherland-Hodgman against a rectangle, winding numbers inside are kept
+pub fn clip_rect(vertices: &[Point2f], rect: Rect2f) -> Vec<Point2f> {
+    // nothing to do if the ring is inside already
+    if vertices.iter().all(|p| {
+        p.x >= rect.lu.x && p.x <= rect.rd.x && p.y >= rect.lu.y && p.y <= rect.rd.y
+    }) {
+        return vertices.to_vec();
+    }
+    let lu = rect.lu;
+    let ru = Point2f::from_floats(rect.rd.x, rect.lu.y);
+    let rd = rect.rd;
+    let ld = Point2f::from_floats(rect.lu.x, rect.rd.y);
+    let mut result = vertices.to_vec();
+    // the kept side of clip_half_plane is inside
+    for (a, b) in [(ld, lu), (lu, ru), (ru, rd), (rd, ld)].iter() {
+        if result.is_empty() {
+            break;
+        }
+        result = clip_half_plane(&result, *a, *b);
+    }
+    result
+}
+
+// Sutherland-Hodgman, clip must be convex, either direction
+pub fn clip_convex(vertices: &[Point2f], clip: &[Point2f]) -> Vec<Point2f> {
+    let mut result = vertices.to_vec();
+    let reversed = signed_area(clip) < 0.;
+    let len = clip.len();
+    for i in 0..len {
+        if result.is_empty() {
+            break;
+        }
+        let (a, b) = if reversed {
+            (clip[(i + 1) % len], clip[i])
+        } else {
+            (clip[i], clip[(i + 1) % len])
+        };
+        result = clip_half_plane(&result, a, b);
+    }
+    result
+}
+
+// split a polyline into parts inside and outside the region
+pub fn clip_polyline(
+    vertices: &[Point2f],
+    region: &[Vec<Point2f>],
+    fill_rule: FillRule,
+) -> (Vec<Vec<Point2f>>, Vec<Vec<Point2f>>) {
+    let (inside, outside) = clip_polyline_params(vertices, region, fill_rule);
+    let points = |parts: Vec<ParamPolyline>| {
+        parts
+            .into_iter()
+            .map(|part| part.into_iter().map(|(p, _)| p).collect())
+            .collect()
+    };
+    (points(inside), points(outside))
+}
+
+// vertices paired with where they lie on the clipped polyline,
+// segment index plus the position along that segment
+pub type ParamPolyline = Vec<(Point2f, f32)>;
+
+// clip_polyline keeping where every vertex comes from
+pub fn clip_polyline_params(
+    vertices: &[Point2f],
+    region: &[Vec<Point2f>],
+    fill_rule: FillRule,
+) -> (Vec<ParamPolyline>, Vec<ParamPolyline>) {
+    let mut inside: Vec<ParamPolyline> = Vec::new();
+    let mut outside: Vec<ParamPolyline> = Vec::new();
+    let mut current: ParamPolyline = Vec::new();
+    let mut current_inside = false;
+    for (i, w) in vertices.windows(2).enumerate() {
+        let mut ts = vec![0., 1.];
+        for contour in region.iter() {
+            let len = contour.len();
+            for j in 0..len {
+                if let Some((t, _)) =
+                    segment_intersection(w[0], w[1], contour[j], contour[(j + 1) % len])
+                {
+                    ts.push(t);
+                }
+            }
+        }
+        ts.sort_by(|x, y| x.total_cmp(y));
+        ts.dedup();
+        for t in ts.windows(2) {
+            let start = w[0] + (w[1] - w[0]) * t[0];
+            let end = w[0] + (w[1] - w[0]) * t[1];
+            let is_inside = contains((start + end) / 2., region, fill_rule);
+            let start = (start, i as f32 + t[0]);
+            if current.is_empty() {
+                current.push(start);
+                current_inside = is_inside;
+            } else if is_inside != current_inside {
+                let finished = std::mem::replace(&mut current, vec![start]);
+                if current_inside {
+                    inside.push(finished);
+                } else {
+                    outside.push(finished);
+                }
+                current_inside = is_inside;
+            }
+            current.push((end, i as f32 + t[1]));
+        }
+    }
+    if current.len() >= 2 {
+        if current_inside {
+            inside.push(current);
+        } else {
+            outside.push(current);
+        }
+    }
+    (inside, outside)
+}
+
+// even-odd contours covering what contours cover nonzero: edges are
+// split where they cross, the pieces inside on one side only are
+// turned to have it on the same side and chained into rings
+pub fn nonzero_outline(contours: &[Vec<Point2f>]) -> Vec<Vec<Point2f>> {
+    let mut edges: Vec<(Point2f, Point2f)> = Vec::new();
+    for contour in contours.iter() {
+        for (i, a) in contour.iter().enumerate() {
+            let b = contour[(i + 1) % contour.len()];
+            if *a != b {
+                edges.push((*a, b));
+            }
+        }
+    }
+    // crossings are found once so both edges get the same point,
+    // a vertex on an edge cuts it at the vertex itself
+    let mut cuts: Vec<Vec<(f32, Point2f)>> = vec![Vec::new(); edges.len()];
+    let inner = |x: f32| x > DEGENERATE_EPS && x < 1. - DEGENERATE_EPS;
+    for i in 0..edges.len() {
+        for j in i + 1..edges.len() {
+            let ((a, b), (c, d)) = (edges[i], edges[j]);
+            if let Some((t, u)) = segment_intersection(a, b, c, d) {
+                let point = if !inner(u) {
+                    if u < 0.5 {
+                        c
+                    } else {
+                        d
+                    }
+                } else if !inner(t) {
+                    if t < 0.5 {
+                        a
+                    } else {
+                        b
+                    }
+                } else {
+                    a + (b - a) * t
+                };
+                if inner(t) {
+                    cuts[i].push((t, point));
+                }
+                if inner(u) {
+                    cuts[j].push((u, point));
+                }
+            }
+        }
+    }
+    let inside = |p: Point2f| contains(p, contours, FillRule::NonZero);
+    let mut pieces: Vec<(Point2f, Point2f)> = Vec::new();
+    for ((a, b), cut) in edges.iter().zip(cuts.iter_mut()) {
+        cut.push((0., *a));
+        cut.push((1., *b));
+        cut.sort_by(|x, y| x.0.total_cmp(&y.0));
+        for w in cut.windows(2) {
+            let (p, q) = (w[0].1, w[1].1);
+            if p == q {
+                continue;
+            }
+            // a small step off the middle to either side
+            let d = q - p;
+            let step = Point2f::from_floats(-d.y, d.x) * 1e-3;
+            let middle = (p + q) / 2.;
+            match (inside(middle + step), inside(middle - step)) {
+                (true, false) => pieces.push((p, q)),
+                (false, true) => pieces.push((q, p)),
+                _ => {}
+            }
+        }
+    }
+    // every point has as many pieces leaving as arriving, so walks
+    // from any piece end where they started
+    let key = |p: Point2f| ((p.x + 0.).to_bits(), (p.y + 0.).to_bits());
+    let mut starts: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+    for (i, piece) in pieces.iter().enumerate() {
+        starts.entry(key(piece.0)).or_default().push(i);
+    }
+    let mut used = vec![false; pieces.len()];
+    let mut outline = Vec::new();
+    for first in 0..pieces.len() {
+        if used[first] {
+            continue;
+        }
+        let mut ring = Vec::new();
+        let mut id = first;
+        loop {
+            used[id] = true;
+            ring.push(pieces[id].0);
+            let next = starts
+                .get(&key(pieces[id].1))
+                .and_then(|ids| ids.iter().find(|x| !used[**x]));
+            match next {
+                Some(next) => id = *next,
+                None => break,
+            }
+        }
+        if ring.len() >= 3 {
+            outline.push(ring);
+        }
+    }
+    outline
+}
+
+// intersection of two simple polygons, Sutherland-Hodgman if the region
+// is convex, Greiner-Hormann otherwise
+// None if Greiner-Hormann gave up on degenerate input, as edges
+// overlapping along the direction the region is nudged in, and
+// vertices is not convex to clip the region with instead
+pub fn clip_polygon(vertices: &[Point2f], region: &[Point2f]) -> Option<Vec<Vec<Point2f>>> {
+    if vertices.len() < 3 || region.len() < 3 {
+        return Some(Vec::new());
+    }
+    // a projective map past the horizon leaves nothing sensible to clip
+    let finite = |p: &Point2f| p.x.is_finite() && p.y.is_finite();
+    if !vertices.iter().all(finite) || !region.iter().all(finite) {
+        return Some(Vec::new());
+    }
+    let convex_result = |result: Vec<Point2f>| {
+        if result.len() < 3 {
+            Vec::new()
+        } else {
+            vec![result]
+        }
+    };
+    if is_convex(region) {
+        return Some(convex_result(clip_convex(vertices, region)));
+    }
+    // intersections on vertices are degenerate for Greiner-Hormann,
+    // nudge the region until there is none
+    let size = vertices
+        .iter()
+        .chain(region.iter())
+        .fold(0f32, |max, p| max.max(p.x.abs()).max(p.y.abs()))
+        .max(1.);
+    let mut nudge = 0.;
+    for _ in 0..8 {
+        let nudged: Vec<Point2f> = region
+            .iter()
+            .map(|p| *p + Point2f::from_floats(nudge, nudge * 0.618))
+            .collect();
+        if let Some(result) = greiner_hormann(vertices, &nudged) {
+            return Some(result);
+        }
+        nudge = if nudge == 0. { size * 1e-5 } else { nudge * 2. };
+    }
+    // the intersection is the same the other way around, though
+    // Sutherland-Hodgman joins separate pieces by zero width bridges
+    if is_convex(vertices) {
+        return Some(convex_result(clip_convex(region, vertices)));
+    }
+    None
+}
+
+struct Node {
+    point: Point2f,
+    next: usize,
+    prev: usize,
+    neighbor: Option<usize>,
+    entry: bool,
+    visited: bool,
+}
+
+fn greiner_hormann(subject: &[Point2f], clip: &[Point2f]) -> Option<Vec<Vec<Point2f>>> {
+    let mut nodes: Vec<Node> = Vec::new();
+    let new_node = |point| Node {
+        point,
+        next: 0,
+        prev: 0,
+        neighbor: None,
+        entry: false,
+        visited: false,
+    };
+    let mut subject_inter: Vec<Vec<(f32, usize)>> = vec![Vec::new(); subject.len()];
+    let mut clip_inter: Vec<Vec<(f32, usize)>> = vec![Vec::new(); clip.len()];
+    for i in 0..subject.len() {
+        let a = subject[i];
+        let b = subject[(i + 1) % subject.len()];
+        for j in 0..clip.len() {
+            let c = clip[j];
+            let d = clip[(j + 1) % clip.len()];
+            let r = b - a;
+            let s = d - c;
+            if r.crossx(s).abs() <= DEGENERATE_EPS * r.norm() * s.norm()
+                && r.crossx(c - a).abs() <= DEGENERATE_EPS * r.norm() * (c - a).norm()
+                && segment_overlap(a, b, c, d)
+            {
+                // collinear overlap
+                return None;
+            }
+            if let Some((t, u)) = segment_intersection(a, b, c, d) {
+                let near_end = |x: f32| !(DEGENERATE_EPS..=1. - DEGENERATE_EPS).contains(&x);
+                if near_end(t) || near_end(u) {
+                    return None;
+                }
+                let id = nodes.len();
+                let point = a + r * t;
+                nodes.push(new_node(point));
+                nodes.push(new_node(point));
+                nodes[id].neighbor = Some(id + 1);
+                nodes[id + 1].neighbor = Some(id);
+                subject_inter[i].push((t, id));
+                clip_inter[j].push((u, id + 1));
+            }
+        }
+    }
+    if nodes.is_empty() {
+        if point_in_polygon(subject[0], clip) {
+            return Some(vec![subject.to_vec()]);
+        }
+        if point_in_polygon(clip[0], subject) {
+            return Some(vec![clip.to_vec()]);
+        }
+        return Some(Vec::new());
+    }
+
+    let build_ring = |nodes: &mut Vec<Node>,
+                      ring: &[Point2f],
+                      mut inters: Vec<Vec<(f32, usize)>>,
+                      other: &[Point2f]| {
+        let mut order = Vec::new();
+        for (vertex, inter) in ring.iter().zip(inters.iter_mut()) {
+            order.push(nodes.len());
+            nodes.push(new_node(*vertex));
+            inter.sort_by(|x, y| x.0.total_cmp(&y.0));
+            order.extend(inter.iter().map(|x| x.1));
+        }
+        let len = order.len();
+        let mut inside = point_in_polygon(ring[0], other);
+        for k in 0..len {
+            let id = order[k];
+            nodes[id].next = order[(k + 1) % len];
+            nodes[id].prev = order[(k + len - 1) % len];
+            if nodes[id].neighbor.is_some() {
+                nodes[id].entry = !inside;
+                inside = !inside;
+            }
+        }
+    };
+    build_ring(&mut nodes, subject, subject_inter, clip);
+    build_ring(&mut nodes, clip, clip_inter, subject);
+
+    let mut result = Vec::new();
+    // subject side intersections are the even ids before vertex nodes
+    let mut start = 0;
+    while start < nodes.len() && nodes[start].neighbor.is_some() {
+        if nodes[start].visited {
+            start += 2;
+            continue;
+        }
+        let mut polygon = vec![nodes[start].point];
+        let mut current = start;
+        loop {
+            let neighbor = nodes[current].neighbor.unwrap();
+            nodes[current].visited = true;
+            nodes[neighbor].visited = true;
+            let forward = nodes[current].entry;
+            loop {
+                current = if forward {
+                    nodes[current].next
+                } else {
+                    nodes[current].prev
+                };
+                polygon.push(nodes[current].point);
+                if nodes[current].neighbor.is_some() {
+                    break;
+                }
+            }
+            if current == start || nodes[current].neighbor == Some(start) {
+                break;
+            }
+            current = nodes[current].neighbor.unwrap();
+        }
+        // the last point closes the ring
+        polygon.pop();
+        if polygon.len() >= 3 {
+            result.push(polygon);
+        }
+        start += 2;
+    }
+    Some(result)
+}
+
+// for collinear ab and cd
+fn segment_overlap(a: Point2f, b: Point2f, c: Point2f, d: Point2f) -> bool {
+    let r = b - a;
+    let len2 = r.dotx(r);
+    if len2 == 0. {
+        return false;
+    }
+    let t0 = (c - a).dotx(r) / len2;
+    let t1 = (d - a).dotx(r) / len2;
+    t0.max(t1) > 0. && t0.min(t1) < 1.
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        clip_convex, clip_polygon, clip_polyline, clip_rect, clip_segment_rect, is_convex,
+        signed_area, split_half_plane,
+    };
+    use crate::algebra::{Point2f, Rect2f};
+    use crate::rasterizer::FillRule;
+
+    fn points(floats: &[f32]) -> Vec<Point2f> {
+        floats
+            .chunks(2)
+            .map(|x| Point2f::from_floats(x[0], x[1]))
+            .collect()
+    }
+
+    #[test]
+    fn test_clip_convex() {
+        let square = points(&[0., 0., 2., 0., 2., 2., 0., 2.]);
+        let region = points(&[1., 1., 3., 1., 3., 3., 1., 3.]);
+        let result = clip_convex(&square, &region);
+        assert!((signed_area(&result) - 1.).abs() < 1e-5);
+        // clockwise region gives the same result
+        let reversed: Vec<Point2f> = region.iter().rev().cloned().collect();
+        let result = clip_convex(&square, &reversed);
+        assert!((signed_area(&result) - 1.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_clip_concave() {
+        // U shape, the bar cuts both arms
+        let u_shape = points(&[
+            0., 0., 3., 0., 3., 3., 2., 3., 2., 1., 1., 1., 1., 3., 0., 3.,
+        ]);
+        assert!(!is_convex(&u_shape));
+        let bar = points(&[-1., 2., 4., 2., 4., 2.5, -1., 2.5]);
+        let result = clip_polygon(&bar, &u_shape).unwrap();
+        assert_eq!(result.len(), 2);
+        let area: f32 = result.iter().map(|x| signed_area(x).abs()).sum();
+        assert!((area - 1.).abs() < 1e-3);
+
+        let line = points(&[-1., 2., 4., 2.]);
+        let (inside, outside) = clip_polyline(&line, std::slice::from_ref(&u_shape), FillRule::EvenOdd);
+        assert_eq!(inside.len(), 2);
+        assert_eq!(outside.len(), 3);
+
+        // cutting both arms leaves them apart, the base stays whole
+        let a = Point2f::from_floats(0., 2.);
+        let b = Point2f::from_floats(3., 2.);
+        let arms = split_half_plane(&u_shape, a, b);
+        assert_eq!(arms.len(), 2);
+        for arm in arms.iter() {
+            assert!((signed_area(arm).abs() - 1.).abs() < 1e-5);
+        }
+        let base = split_half_plane(&u_shape, b, a);
+        assert_eq!(base.len(), 1);
+        assert!((signed_area(&base[0]).abs() - 5.).abs() < 1e-5);
+
+        // nothing to clip past the horizon
+        let broken = points(&[0., 0., f32::NAN, 1., 1., 1.]);
+        assert!(clip_polygon(&broken, &u_shape).unwrap().is_empty());
+        let (inside, _) = clip_polyline(&broken, &[u_shape], FillRule::EvenOdd);
+        assert!(inside.is_empty());
+    }
+
+    #[test]
+    fn test_clip_collinear() {
+        let area = |result: Vec<Vec<Point2f>>| -> f32 {
+            result.iter().map(|x| signed_area(x).abs()).sum()
+        };
+        // an L on the base of the U, overlapping it along y = 0,
+        // taken apart by nudging
+        let u_shape = points(&[
+            0., 0., 3., 0., 3., 3., 2., 3., 2., 1., 1., 1., 1., 3., 0., 3.,
+        ]);
+        let l_shape = points(&[-1., 0., 4., 0., 4., 0.5, 0.5, 0.5, 0.5, 2., -1., 2.]);
+        assert!(!is_convex(&l_shape));
+        let result = clip_polygon(&l_shape, &u_shape).unwrap();
+        assert!((area(result) - 2.25).abs() < 1e-3);
+
+        // along the nudge direction the overlap stays, a convex
+        // subject still clips the region
+        let dart = points(&[0., 0., 10., 6.18, 10., -5., 5., -1.]);
+        assert!(!is_convex(&dart));
+        let triangle = points(&[0., 0., 10., 6.18, 10., 0.]);
+        let result = clip_polygon(&triangle, &dart).unwrap();
+        assert!((area(result) - 30.9).abs() < 1e-3);
+        // two darts touching along it are given up on
+        let other = points(&[0., 0., 10., 6.18, 0., 10., 3., 5.]);
+        assert!(!is_convex(&other));
+        assert!(clip_polygon(&other, &dart).is_none());
+    }
+
+    #[test]
+    fn test_clip_rect() {
+        let eps: f32 = 1e-3;
+        let rect = Rect2f::from_floats(0., 0., 10., 10.);
+        let (a, b) = clip_segment_rect(
+            Point2f::from_floats(-1e5, 5.),
+            Point2f::from_floats(1e5, 5.),
+            rect,
+        )
+        .unwrap();
+        assert!((a - Point2f::from_floats(0., 5.)).norm() < eps);
+        assert!((b - Point2f::from_floats(10., 5.)).norm() < eps);
+        assert!(clip_segment_rect(
+            Point2f::from_floats(-1., -1.),
+            Point2f::from_floats(11., -1.),
+            rect
+        )
+        .is_none());
+
+        // huge triangle covering the rectangle
+        let triangle = points(&[-1e6, -1e6, 1e6, -1e6, 0., 1e6]);
+        let result = clip_rect(&triangle, rect);
+        assert!((signed_area(&result).abs() - 100.).abs() < eps);
+    }
+}
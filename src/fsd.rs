@@ -7,12 +7,12 @@ pub fn fsd(c: char) -> GraphicObjects {
         () => {
             "0 0 1 0.7 "
         };
-    };
+    }
     macro_rules! default_border_color {
         () => {
             "0 1 0 0.7 "
         };
-    };
+    }
     lazy_static! {
         static ref SEGMENTS: Vec<GraphicObjects> = vec![
             GraphicObjects::from_strs(vec![concat!(
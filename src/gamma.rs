@@ -0,0 +1,120 @@
+// entries of the encode table
+const ENCODE_SIZE: usize = 4096;
+
+// lookup tables between encoded bytes and linear light, so canvas
+// blending does not need a powf per pixel
+#[derive(Clone, Debug)]
+pub struct Gamma {
+    // encoded byte to linear in [0, 1]
+    decode: Vec<f32>,
+    // indexed by sqrt(linear), dark values far apart once encoded
+    // get enough entries that way, encoded in [0, 255] not rounded
+    // so callers can round or dither
+    encode: Vec<f32>,
+}
+
+impl Gamma {
+    // the piecewise curve of srgb
+    pub fn srgb() -> Gamma {
+        Gamma::build(
+            |v| {
+                if v <= 0.04045 {
+                    v / 12.92
+                } else {
+                    ((v + 0.055) / 1.055).powf(2.4)
+                }
+            },
+            |v| {
+                if v <= 0.0031308 {
+                    v * 12.92
+                } else {
+                    1.055 * v.powf(1. / 2.4) - 0.055
+                }
+            },
+        )
+    }
+
+    // linear = encoded ^ exponent, 2.2 is close to srgb
+    pub fn power(exponent: f32) -> Gamma {
+        if exponent <= 0. {
+            panic!("bad gamma");
+        }
+        Gamma::build(|v| v.powf(exponent), |v| v.powf(1. / exponent))
+    }
+
+    fn build(to_linear: impl Fn(f32) -> f32, to_encoded: impl Fn(f32) -> f32) -> Gamma {
+        let decode = (0..256).map(|i| to_linear(i as f32 / 255.)).collect();
+        let encode = (0..ENCODE_SIZE)
+            .map(|i| {
+                let s = i as f32 / (ENCODE_SIZE - 1) as f32;
+                (to_encoded(s * s) * 255.).clamp(0., 255.)
+            })
+            .collect();
+        Gamma { decode, encode }
+    }
+
+    // encoded in [0, 1] to linear in [0, 1]
+    #[inline]
+    pub fn decode(&self, v: f32) -> f32 {
+        self.decode[(v * 255.).round().clamp(0., 255.) as usize]
+    }
+
+    // linear in [0, 1] to encoded in [0, 255]
+    #[inline]
+    pub fn encode(&self, v: f32) -> f32 {
+        let index = v.clamp(0., 1.).sqrt() * (ENCODE_SIZE - 1) as f32;
+        self.encode[index.round() as usize]
+    }
+
+    pub fn decode_color(&self, color: [f32; 3]) -> [f32; 3] {
+        [self.decode(color[0]), self.decode(color[1]), self.decode(color[2])]
+    }
+
+    // premultiplied encoded in [0, 255] to premultiplied linear in [0, 1]
+    // alpha is linear already
+    pub(crate) fn pixel_to_linear(&self, pixel: [f32; 4]) -> [f32; 4] {
+        let alpha = pixel[3] / 255.;
+        let mut linear = [0., 0., 0., alpha];
+        if alpha > 0. {
+            for (l, c) in linear.iter_mut().zip(pixel.iter()).take(3) {
+                *l = self.decode(c / pixel[3]) * alpha;
+            }
+        }
+        linear
+    }
+
+    // the other way around of pixel_to_linear
+    pub(crate) fn linear_to_pixel(&self, linear: [f32; 4]) -> [f32; 4] {
+        let alpha = linear[3].clamp(0., 1.);
+        let mut pixel = [0., 0., 0., alpha * 255.];
+        if alpha > 0. {
+            for (p, l) in pixel.iter_mut().zip(linear.iter()).take(3) {
+                *p = self.encode(l / alpha) * alpha;
+            }
+        }
+        pixel
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Gamma;
+
+    #[test]
+    fn test_gamma() {
+        // every byte survives decoding and encoding back
+        for gamma in [Gamma::srgb(), Gamma::power(2.2)].iter() {
+            for i in 0..256 {
+                let v = i as f32 / 255.;
+                assert_eq!(gamma.encode(gamma.decode(v)).round(), i as f32);
+            }
+        }
+        let srgb = Gamma::srgb();
+        assert_eq!(srgb.encode(0.5).round(), 188.);
+        assert!((srgb.decode(188. / 255.) - 0.5).abs() < 0.005);
+        // half covered white over black
+        let pixel = srgb.linear_to_pixel([0.5, 0.5, 0.5, 1.]).map(|c| c.round());
+        assert_eq!(pixel, [188., 188., 188., 255.]);
+        assert_eq!(srgb.pixel_to_linear([0.; 4]), [0.; 4]);
+    }
+}
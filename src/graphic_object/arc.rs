@@ -0,0 +1,583 @@
+use std::any::Any;
+use std::f32::consts::PI;
+
+use crate::algebra::{Mat2x2f, Mat3x3f, Point2f, Rect2f};
+use crate::blend::BlendMode;
+use crate::canvas::Canvas;
+use crate::rasterizer::FillRule;
+use super::{GraphicObject, LineSegs2f, Polygon2f};
+
+// max distance from the tessellated arc to the real one, in pixels
+const RENDER_TOLERANCE: f32 = 0.25;
+// tessellation tolerance relative to the radius for non-affine transforms
+const TRANSFORM_TOLERANCE_K: f32 = 1e-3;
+// smallest tolerance relative to the radius, so a zero tolerance
+// does not run out of memory
+const MIN_TOLERANCE_K: f32 = 1e-6;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ArcMode {
+    // the curve only
+    Stroke,
+    // filled pie slice from the center
+    Sector,
+}
+
+// angle step keeping the chord within tolerance of a circle of radius r
+fn angle_step(r: f32, tolerance: f32) -> f32 {
+    let tolerance = tolerance.max(r * MIN_TOLERANCE_K);
+    if tolerance >= r {
+        PI / 2.
+    } else {
+        (2. * (1. - tolerance / r).acos()).min(PI / 2.)
+    }
+}
+
+// pieces of a sweep with chords within tolerance
+fn arc_split(sweep: f32, r: f32, tolerance: f32) -> u32 {
+    (sweep.abs() / angle_step(r, tolerance)).ceil().max(1.) as u32
+}
+
+// what is drawn of a sweep, past a full turn the curve only repeats
+fn drawn_sweep(sweep: f32) -> f32 {
+    sweep.clamp(-2. * PI, 2. * PI)
+}
+
+// if theta is covered by the sweep from start
+fn in_sweep(theta: f32, start: f32, sweep: f32) -> bool {
+    if sweep.abs() >= 2. * PI {
+        true
+    } else if sweep >= 0. {
+        (theta - start).rem_euclid(2. * PI) <= sweep
+    } else {
+        (start - theta).rem_euclid(2. * PI) <= -sweep
+    }
+}
+
+fn bounds_of(points: impl Iterator<Item = Point2f>) -> Rect2f {
+    let mut rect = Rect2f::from_floats(
+        f32::INFINITY,
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+        f32::NEG_INFINITY,
+    );
+    for point in points {
+        rect.lu.x = rect.lu.x.min(point.x);
+        rect.lu.y = rect.lu.y.min(point.y);
+        rect.rd.x = rect.rd.x.max(point.x);
+        rect.rd.y = rect.rd.y.max(point.y);
+    }
+    rect
+}
+
+// circular arc, angles in radians, positive sweep goes from x axis to y axis
+#[derive(Clone, Debug)]
+pub struct Arc2f {
+    pub center: Point2f,
+    pub r: f32,
+    pub start: f32,
+    pub sweep: f32,
+    pub color: [f32; 4],
+    pub mode: ArcMode,
+    pub blend_mode: BlendMode,
+}
+
+impl Arc2f {
+    pub fn new(
+        center: Point2f,
+        r: f32,
+        start: f32,
+        sweep: f32,
+        color: [f32; 4],
+        mode: ArcMode,
+    ) -> Arc2f {
+        Arc2f {
+            center,
+            r,
+            start,
+            sweep,
+            color,
+            mode,
+            blend_mode: BlendMode::SourceOver,
+        }
+    }
+
+    // arc from p0 to p1, bulge is tan(sweep / 4) as in DXF
+    // None for zero bulge(a straight line) or coincident points
+    pub fn from_bulge(
+        p0: Point2f,
+        p1: Point2f,
+        bulge: f32,
+        color: [f32; 4],
+        mode: ArcMode,
+    ) -> Option<Arc2f> {
+        let chord = p1 - p0;
+        let chord_len = chord.norm();
+        if bulge == 0. || chord_len == 0. {
+            return None;
+        }
+        let sweep = 4. * bulge.atan();
+        let r = chord_len / 2. / (sweep / 2.).sin().abs();
+        // signed distance from the chord midpoint to the center, to the left
+        let offset = chord_len / 2. / (sweep / 2.).tan();
+        let left = Point2f::from_floats(-chord.y, chord.x) / chord_len;
+        let center = (p0 + p1) / 2. + left * offset;
+        let d0 = p0 - center;
+        Some(Arc2f {
+            center,
+            r,
+            start: d0.y.atan2(d0.x),
+            sweep,
+            color,
+            mode,
+            blend_mode: BlendMode::SourceOver,
+        })
+    }
+
+    pub fn point_at_angle(&self, theta: f32) -> Point2f {
+        self.center + Point2f::from_polar(self.r, theta)
+    }
+
+    pub fn start_point(&self) -> Point2f {
+        self.point_at_angle(self.start)
+    }
+
+    pub fn end_point(&self) -> Point2f {
+        self.point_at_angle(self.start + self.sweep)
+    }
+
+    pub fn length(&self) -> f32 {
+        self.r * self.sweep.abs()
+    }
+
+    pub fn bounds(&self) -> Rect2f {
+        let end = self.start + self.sweep;
+        let extremes = (0..4)
+            .map(|k| k as f32 * PI / 2.)
+            .filter(|theta| in_sweep(*theta, self.start, self.sweep))
+            .map(|theta| self.point_at_angle(theta));
+        bounds_of(
+            vec![self.start_point(), self.point_at_angle(end)]
+                .into_iter()
+                .chain(extremes),
+        )
+    }
+
+    // vertices along the arc, chords are within tolerance of the curve
+    pub fn tessellate(&self, tolerance: f32) -> Vec<Point2f> {
+        let sweep = drawn_sweep(self.sweep);
+        let split = arc_split(sweep, self.r, tolerance);
+        (0..=split)
+            .map(|i| self.point_at_angle(self.start + sweep * i as f32 / split as f32))
+            .collect()
+    }
+
+    pub fn to_elliptic(&self) -> EllipticArc2f {
+        EllipticArc2f {
+            center: self.center,
+            radii: Point2f::from_floats(self.r, self.r),
+            rotation: 0.,
+            start: self.start,
+            sweep: self.sweep,
+            color: self.color,
+            mode: self.mode,
+            blend_mode: self.blend_mode,
+        }
+    }
+}
+
+// elliptical arc, start and sweep are parametric angles:
+// p(t) = center + rotate(rotation) * (radii.x * cos(t), radii.y * sin(t))
+#[derive(Clone, Debug)]
+pub struct EllipticArc2f {
+    pub center: Point2f,
+    pub radii: Point2f,
+    pub rotation: f32,
+    pub start: f32,
+    pub sweep: f32,
+    pub color: [f32; 4],
+    pub mode: ArcMode,
+    pub blend_mode: BlendMode,
+}
+
+impl EllipticArc2f {
+    pub fn new(
+        center: Point2f,
+        radii: Point2f,
+        rotation: f32,
+        start: f32,
+        sweep: f32,
+        color: [f32; 4],
+        mode: ArcMode,
+    ) -> EllipticArc2f {
+        EllipticArc2f {
+            center,
+            radii,
+            rotation,
+            start,
+            sweep,
+            color,
+            mode,
+            blend_mode: BlendMode::SourceOver,
+        }
+    }
+
+    pub fn point_at(&self, t: f32) -> Point2f {
+        self.center
+            + Mat2x2f::from_theta(self.rotation)
+                * Point2f::from_floats(self.radii.x * t.cos(), self.radii.y * t.sin())
+    }
+
+    pub fn start_point(&self) -> Point2f {
+        self.point_at(self.start)
+    }
+
+    pub fn end_point(&self) -> Point2f {
+        self.point_at(self.start + self.sweep)
+    }
+
+    // Simpson's rule, there is no closed form
+    pub fn length(&self) -> f32 {
+        let split = 64 * (self.sweep.abs() / (PI / 2.)).ceil().max(1.) as u32;
+        let h = self.sweep / split as f32;
+        let speed = |t: f32| {
+            Point2f::from_floats(self.radii.x * t.sin(), self.radii.y * t.cos()).norm()
+        };
+        let mut sum = speed(self.start) + speed(self.start + self.sweep);
+        for i in 1..split {
+            let k = if i % 2 == 1 { 4. } else { 2. };
+            sum += k * speed(self.start + h * i as f32);
+        }
+        (sum * h / 3.).abs()
+    }
+
+    pub fn bounds(&self) -> Rect2f {
+        let (sin, cos) = self.rotation.sin_cos();
+        // zeros of dx/dt and dy/dt
+        let tx = (-self.radii.y * sin).atan2(self.radii.x * cos);
+        let ty = (self.radii.y * cos).atan2(self.radii.x * sin);
+        let extremes = vec![tx, tx + PI, ty, ty + PI]
+            .into_iter()
+            .filter(|t| in_sweep(*t, self.start, self.sweep))
+            .map(|t| self.point_at(t));
+        bounds_of(
+            vec![self.start_point(), self.end_point()]
+                .into_iter()
+                .chain(extremes),
+        )
+    }
+
+    pub fn tessellate(&self, tolerance: f32) -> Vec<Point2f> {
+        let r = self.radii.x.abs().max(self.radii.y.abs());
+        let sweep = drawn_sweep(self.sweep);
+        let split = arc_split(sweep, r, tolerance);
+        (0..=split)
+            .map(|i| self.point_at(self.start + sweep * i as f32 / split as f32))
+            .collect()
+    }
+
+    // exact image under a linear map, found by 2x2 svd
+    pub fn affine(&self, mat: Mat2x2f) -> EllipticArc2f {
+        let rotation = Mat2x2f::from_theta(self.rotation);
+        // mat * rotation * diag(radii)
+        let a = Mat2x2f {
+            x1: (mat.x1 * rotation.x1 + mat.x2 * rotation.y1) * self.radii.x,
+            x2: (mat.x1 * rotation.x2 + mat.x2 * rotation.y2) * self.radii.y,
+            y1: (mat.y1 * rotation.x1 + mat.y2 * rotation.y1) * self.radii.x,
+            y2: (mat.y1 * rotation.x2 + mat.y2 * rotation.y2) * self.radii.y,
+        };
+        // a = rotate(phi) * diag(sx, sy) * rotate(theta)
+        let e = (a.x1 + a.y2) / 2.;
+        let f = (a.x1 - a.y2) / 2.;
+        let g = (a.y1 + a.x2) / 2.;
+        let h = (a.y1 - a.x2) / 2.;
+        let q = (e * e + h * h).sqrt();
+        let r = (f * f + g * g).sqrt();
+        let a1 = g.atan2(f);
+        let a2 = h.atan2(e);
+        let theta = (a2 - a1) / 2.;
+        let phi = (a2 + a1) / 2.;
+        let sy = q - r;
+        // a negative sy mirrors the parameter
+        let (start, sweep) = if sy < 0. {
+            (-(self.start + theta), -self.sweep)
+        } else {
+            (self.start + theta, self.sweep)
+        };
+        EllipticArc2f {
+            center: mat * self.center,
+            radii: Point2f::from_floats(q + r, sy.abs()),
+            rotation: phi,
+            start,
+            sweep,
+            color: self.color,
+            mode: self.mode,
+            blend_mode: self.blend_mode,
+        }
+    }
+
+    // back to a circular arc if the radii are the same
+    fn simplified(self) -> Box<dyn GraphicObject> {
+        let (rx, ry) = (self.radii.x, self.radii.y);
+        if (rx - ry).abs() <= rx.max(ry) * 1e-6 {
+            Box::new(Arc2f {
+                center: self.center,
+                r: (rx + ry) / 2.,
+                start: self.start + self.rotation,
+                sweep: self.sweep,
+                color: self.color,
+                mode: self.mode,
+                blend_mode: self.blend_mode,
+            })
+        } else {
+            Box::new(self)
+        }
+    }
+
+    // polyline for Stroke, polygon for Sector
+    pub fn to_graphic_object(&self, tolerance: f32) -> Box<dyn GraphicObject> {
+        let mut vertices = self.tessellate(tolerance);
+        match self.mode {
+            ArcMode::Stroke => {
+                let mut line_segs = LineSegs2f::new(vertices, self.color);
+                line_segs.blend_mode = self.blend_mode;
+                Box::new(line_segs)
+            }
+            ArcMode::Sector => {
+                vertices.push(self.center);
+                let mut polygon2f = Polygon2f::new(vertices, self.color, [0., 0., 0., 0.]);
+                // a full turn closes back over the center edge
+                polygon2f.fill_rule = FillRule::NonZero;
+                polygon2f.blend_mode = self.blend_mode;
+                Box::new(polygon2f)
+            }
+        }
+    }
+}
+
+impl GraphicObject for Arc2f {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn shift(&self, dp: Point2f) -> Box<dyn GraphicObject> {
+        Box::new(Arc2f {
+            center: self.center + dp,
+            ..self.clone()
+        })
+    }
+
+    fn rotate(&self, rotate_mat: Mat2x2f) -> Box<dyn GraphicObject> {
+        self.to_elliptic().affine(rotate_mat).simplified()
+    }
+
+    fn zoom(&self, k: f32) -> Box<dyn GraphicObject> {
+        self.to_elliptic()
+            .affine(Mat2x2f {
+                x1: k,
+                x2: 0.,
+                y1: 0.,
+                y2: k,
+            })
+            .simplified()
+    }
+
+    fn shear(&self, k: f32) -> Box<dyn GraphicObject> {
+        self.to_elliptic().shear(k)
+    }
+
+    fn projective(&self, mat: Mat3x3f, max_len: Option<f32>) -> Box<dyn GraphicObject> {
+        self.to_elliptic().projective(mat, max_len)
+    }
+
+    fn warp(&self, f: &dyn Fn(Point2f) -> Point2f, tolerance: f32) -> Box<dyn GraphicObject> {
+        self.to_elliptic().warp(f, tolerance)
+    }
+
+    fn render(&self, canvas: &mut Canvas) {
+        self.to_elliptic().render(canvas);
+    }
+}
+
+impl GraphicObject for EllipticArc2f {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn shift(&self, dp: Point2f) -> Box<dyn GraphicObject> {
+        Box::new(EllipticArc2f {
+            center: self.center + dp,
+            ..self.clone()
+        })
+    }
+
+    fn rotate(&self, rotate_mat: Mat2x2f) -> Box<dyn GraphicObject> {
+        Box::new(self.affine(rotate_mat))
+    }
+
+    fn zoom(&self, k: f32) -> Box<dyn GraphicObject> {
+        Box::new(self.affine(Mat2x2f {
+            x1: k,
+            x2: 0.,
+            y1: 0.,
+            y2: k,
+        }))
+    }
+
+    fn shear(&self, k: f32) -> Box<dyn GraphicObject> {
+        Box::new(self.affine(Mat2x2f {
+            x1: 1.,
+            x2: k,
+            y1: 0.,
+            y2: 1.,
+        }))
+    }
+
+    fn projective(&self, mat: Mat3x3f, max_len: Option<f32>) -> Box<dyn GraphicObject> {
+        let r = self.radii.x.abs().max(self.radii.y.abs());
+        self.to_graphic_object(r * TRANSFORM_TOLERANCE_K)
+            .projective(mat, max_len)
+    }
+
+    fn warp(&self, f: &dyn Fn(Point2f) -> Point2f, tolerance: f32) -> Box<dyn GraphicObject> {
+        self.to_graphic_object(tolerance).warp(f, tolerance)
+    }
+
+    fn render(&self, canvas: &mut Canvas) {
+        self.to_graphic_object(RENDER_TOLERANCE / canvas.scaler)
+            .render(canvas);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Arc2f, ArcMode, EllipticArc2f};
+    use crate::algebra::{Mat2x2f, Mat3x3f, Point2f};
+    use crate::blend::BlendMode;
+    use crate::canvas::Canvas;
+    use crate::graphic_object::{FillRule, GraphicObject, Polygon2f};
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_arc2f() {
+        let eps: f32 = 1e-4;
+        let p0 = Point2f::from_floats(1., 0.);
+        let p1 = Point2f::from_floats(-1., 0.);
+        // counterclockwise half circle through (0, 1)
+        let arc = Arc2f::from_bulge(p0, p1, 1., [1.; 4], ArcMode::Stroke).unwrap();
+        assert!(arc.center.norm() < eps);
+        assert!((arc.r - 1.).abs() < eps);
+        assert!((arc.sweep - PI).abs() < eps);
+        assert!((arc.end_point() - p1).norm() < eps);
+        assert!((arc.length() - PI).abs() < eps);
+        let bounds = arc.bounds();
+        assert!((bounds.lu - Point2f::from_floats(-1., 0.)).norm() < eps);
+        assert!((bounds.rd - Point2f::from_floats(1., 1.)).norm() < eps);
+        assert!(Arc2f::from_bulge(p0, p1, 0., [1.; 4], ArcMode::Stroke).is_none());
+
+        // tiny arcs still have a segment
+        let tiny = Arc2f::new(Point2f::new(), 0.01, 0., 0.01, [1.; 4], ArcMode::Stroke);
+        assert_eq!(tiny.tessellate(0.25).len(), 2);
+
+        // a zero tolerance is bounded rather than endless
+        let circle = Arc2f::new(Point2f::new(), 1., 0., 2. * PI, [1.; 4], ArcMode::Stroke);
+        let points = circle.tessellate(0.);
+        assert!(points.len() > 1000 && points.len() < 4000);
+        let points = circle.to_elliptic().tessellate(0.);
+        assert!(points.len() > 1000 && points.len() < 4000);
+    }
+
+    #[test]
+    fn test_huge_sweep() {
+        let eps: f32 = 1e-3;
+        // more than a full turn is drawn as one turn, on the ellipse
+        let center = Point2f::from_floats(1., 2.);
+        let (rx, ry, rotation) = (2., 1., 0.3);
+        let arc = EllipticArc2f::new(
+            center,
+            Point2f::from_floats(rx, ry),
+            rotation,
+            0.5,
+            1e9,
+            [1.; 4],
+            ArcMode::Stroke,
+        );
+        let points = arc.tessellate(0.01);
+        let mut turned = 0.;
+        let mut last: Option<f32> = None;
+        for p in points.iter() {
+            let local = Mat2x2f::from_theta(-rotation) * (*p - center);
+            let (x, y) = (local.x / rx, local.y / ry);
+            assert!((x * x + y * y - 1.).abs() < eps);
+            let t = y.atan2(x);
+            if let Some(last) = last {
+                let step = (t - last).rem_euclid(2. * PI);
+                assert!(step < PI / 2.);
+                turned += step;
+            }
+            last = Some(t);
+        }
+        assert!((turned - 2. * PI).abs() < eps);
+        assert!((points[0] - arc.start_point()).norm() < eps);
+
+        // the overlap of a sector past a full turn is not a hole
+        for sweep in [6., 7., 1e9].iter() {
+            let mut canvas = Canvas::new((4, 4), 1.);
+            let sector = Arc2f::new(
+                Point2f::from_floats(2., 2.),
+                1.5,
+                0.,
+                *sweep,
+                [1.; 4],
+                ArcMode::Sector,
+            );
+            let polygon2f = sector.to_elliptic().to_graphic_object(0.25);
+            let polygon2f = polygon2f.as_any().downcast_ref::<Polygon2f>().unwrap();
+            assert_eq!(polygon2f.fill_rule, FillRule::NonZero);
+            sector.render(&mut canvas);
+            for (x, y) in [(1, 1), (2, 1), (1, 2), (2, 2)].iter() {
+                assert_eq!(canvas.data[(y * 4 + x) * 3], 255, "{} {} {}", sweep, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_elliptic_affine() {
+        let eps: f32 = 1e-4;
+        let arc = Arc2f::new(
+            Point2f::from_floats(1., 2.),
+            2.,
+            0.3,
+            2.,
+            [1.; 4],
+            ArcMode::Stroke,
+        )
+        .to_elliptic();
+        let mat = Mat2x2f {
+            x1: 1.,
+            x2: 0.7,
+            y1: -0.2,
+            y2: -1.5,
+        };
+        let mapped = arc.affine(mat);
+        for i in 0..=8 {
+            let t = arc.start + arc.sweep * i as f32 / 8.;
+            let t_mapped = mapped.start + mapped.sweep * i as f32 / 8.;
+            assert!((mat * arc.point_at(t) - mapped.point_at(t_mapped)).norm() < eps);
+        }
+    }
+
+    #[test]
+    fn test_blend_mode() {
+        // the mode lasts through transforms down to what is rendered
+        let mut arc = Arc2f::new(Point2f::new(), 1., 0., PI, [1.; 4], ArcMode::Sector);
+        arc.blend_mode = BlendMode::Multiply;
+        let rotated = arc.rotate(Mat2x2f::from_theta(1.));
+        let rotated = rotated.as_any().downcast_ref::<Arc2f>().unwrap();
+        assert_eq!(rotated.blend_mode, BlendMode::Multiply);
+        let sheared = arc.shear(0.5);
+        let polygon2f = sheared.projective(Mat3x3f::identity(), None);
+        let polygon2f = polygon2f.as_any().downcast_ref::<Polygon2f>().unwrap();
+        assert_eq!(polygon2f.blend_mode, BlendMode::Multiply);
+    }
+}
@@ -1,17 +1,90 @@
 use std::any::Any;
 
-use crate::algebra::{Point2f, Mat2x2f};
+use crate::algebra::{Point2f, Mat2x2f, Mat3x3f};
+use crate::blend::BlendMode;
 use crate::canvas::Canvas;
-use super::{GraphicObject};
+use crate::clip;
+use crate::paint::mix_colors;
+use super::{
+    interpolate_colors, point_at_param, subdivide_params, warp_params, GraphicObject, Polygon2f,
+};
 
 #[derive(Clone, Debug)]
 pub struct LineSegs2f {
     pub vertices: Vec<Point2f>,
     pub color: [f32; 4], // rgba
+    // one rgba per vertex, interpolated along segments, replaces color
+    pub vertex_colors: Option<Vec<[f32; 4]>>,
+    pub blend_mode: BlendMode,
 }
+
+// one column of a wu line, x along the major axis
+#[derive(Copy, Clone, Debug)]
+struct WuColumn {
+    x: i32,
+    y: f32,
+    // length of the line inside the column along the major axis
+    coverage: f32,
+    steep: bool,
+    color: Option<[f32; 4]>,
+    // across the major axis in pixels, wider than 1 when supersampled
+    width: f32,
+}
+
+impl WuColumn {
+    // the coverage of other added at the place of self
+    fn merge(self, other: WuColumn) -> WuColumn {
+        WuColumn {
+            coverage: self.coverage + other.coverage,
+            ..self
+        }
+    }
+
+    fn plot(&self, alpha: f32, canvas: &mut Canvas) {
+        let mut alpha = alpha * self.coverage.min(1.);
+        if let Some(color) = self.color {
+            canvas.set_color([color[0], color[1], color[2]]);
+            alpha *= color[3];
+        }
+        // pixels overlapped by a box of width around y, pixel centers
+        // are at integers, width 1 splits 1 - fpart and fpart as wu
+        let (lo, hi) = (self.y - self.width / 2., self.y + self.width / 2.);
+        for y in (lo + 0.5).floor() as i32..=(hi + 0.5).floor() as i32 {
+            let overlap = hi.min(y as f32 + 0.5) - lo.max(y as f32 - 0.5);
+            if self.steep {
+                canvas.putpixel(y, self.x, alpha * overlap);
+            } else {
+                canvas.putpixel(self.x, y, alpha * overlap);
+            }
+        }
+    }
+}
+
 impl LineSegs2f {
     pub fn new(vertices: Vec<Point2f>, color: [f32; 4]) -> LineSegs2f {
-        LineSegs2f { vertices, color }
+        LineSegs2f {
+            vertices,
+            color,
+            vertex_colors: None,
+            blend_mode: BlendMode::SourceOver,
+        }
+    }
+
+    // color is kept for the text format only
+    pub fn with_vertex_colors(
+        vertices: Vec<Point2f>,
+        color: [f32; 4],
+        vertex_colors: Vec<[f32; 4]>,
+    ) -> LineSegs2f {
+        if vertices.len() != vertex_colors.len() {
+            panic!("vertex color count mismatch");
+        }
+        LineSegs2f {
+            vertices,
+            color,
+            vertex_colors: Some(vertex_colors),
+            blend_mode: BlendMode::SourceOver,
+        }
     }
 
     pub fn from_floats(floats: Vec<f32>) -> LineSegs2f {
@@ -36,120 +109,343 @@ impl LineSegs2f {
         LineSegs2f::new(vertices, color)
     }
 
+    // r g b a followed by x y r g b a for every vertex
+    pub fn from_colored_floats(floats: Vec<f32>) -> LineSegs2f {
+        if floats.len() < 4 || floats.len() % 6 != 4 {
+            panic!("colored parse");
+        }
+        let color = [floats[0], floats[1], floats[2], floats[3]];
+        let (vertices, vertex_colors) = floats[4..]
+            .chunks(6)
+            .map(|x| (Point2f::from_floats(x[0], x[1]), [x[2], x[3], x[4], x[5]]))
+            .unzip();
+        LineSegs2f::with_vertex_colors(vertices, color, vertex_colors)
+    }
+
+    // text format, see GraphicObjects::from_strs
+    pub fn to_str(&self) -> String {
+        let mut tokens: Vec<String> = vec!["l".to_string()];
+        if self.blend_mode != BlendMode::SourceOver {
+            tokens.push("blend".to_string());
+            tokens.push(self.blend_mode.to_str().to_string());
+        }
+        if self.vertex_colors.is_some() {
+            tokens.push("colored".to_string());
+        }
+        tokens.extend(self.color.iter().map(|x| x.to_string()));
+        for (i, vertex) in self.vertices.iter().enumerate() {
+            tokens.push(vertex.x.to_string());
+            tokens.push(vertex.y.to_string());
+            if let Some(vertex_colors) = &self.vertex_colors {
+                tokens.extend(vertex_colors[i].iter().map(|x| x.to_string()));
+            }
+        }
+        tokens.join(" ")
+    }
+
     #[inline]
     pub fn shift(&self, dp: Point2f) -> LineSegs2f {
+        self.map_vertices(|x| x + dp)
+    }
+
+    // same colors, vertices moved one by one
+    fn map_vertices(&self, f: impl Fn(Point2f) -> Point2f) -> LineSegs2f {
         LineSegs2f {
-            vertices: self.vertices.iter().map(|x| *x + dp).collect(),
+            vertices: self.vertices.iter().map(|x| f(*x)).collect(),
             color: self.color,
+            vertex_colors: self.vertex_colors.clone(),
+            blend_mode: self.blend_mode,
         }
     }
 
-    #[inline]
-    fn wu(x1: f32, y1: f32, x2: f32, y2: f32, color: [f32; 4], canvas: &mut Canvas) {
-        let mut x1: i32 = (x1 * canvas.scaler) as i32;
-        let mut y1: i32 = (y1 * canvas.scaler) as i32;
-        let mut x2: i32 = (x2 * canvas.scaler) as i32;
-        let mut y2: i32 = (y2 * canvas.scaler) as i32;
-        let mut dx = x2 - x1;
-        let dy = y2 - y1;
-        canvas.set_color([color[0], color[1], color[2]]);
-        if dx == 0 {
-            if dy < 0 {
-                std::mem::swap(&mut y1, &mut y2);
-            }
-            for y in y1..y2 + 1 {
-                canvas.putpixel(x1, y, color[3]);
-            }
-            return;
+    // new vertices at positions as in subdivide_params, colors follow
+    fn with_params(&self, vertices: Vec<Point2f>, params: &[f32]) -> LineSegs2f {
+        LineSegs2f {
+            vertices,
+            color: self.color,
+            vertex_colors: self
+                .vertex_colors
+                .as_ref()
+                .map(|colors| interpolate_colors(colors, params)),
+            blend_mode: self.blend_mode,
         }
+    }
 
-        if dy == 0 {
-            if dx < 0 {
-                std::mem::swap(&mut x1, &mut x2);
-            }
-            for x in x1..x2 + 1 {
-                canvas.putpixel(x, y1, color[3]);
-            }
-            return;
-        }
+    // total arc length of the polyline
+    pub fn length(&self) -> f32 {
+        self.vertices
+            .windows(2)
+            .map(|w| (w[1] - w[0]).norm())
+            .sum()
+    }
 
-        if dx == dy {
-            if dx < 0 {
-                x1 = x2;
-                y1 = y2;
-                dx = -dx;
+    // point and unit tangent at arc length distance from the first vertex
+    // distance is clamped to [0, length], None for less than 2 vertices
+    // or a polyline of zero length
+    pub fn sample_at(&self, distance: f32) -> Option<(Point2f, Point2f)> {
+        let mut rest = distance.max(0.);
+        let mut last: Option<(Point2f, Point2f)> = None;
+        for w in self.vertices.windows(2) {
+            let d = w[1] - w[0];
+            let len = d.norm();
+            // zero-length segments have no tangent
+            if len == 0. {
+                continue;
             }
-            for i in 0..dx + 1 {
-                canvas.putpixel(x1 + i, y1 + i, color[3]);
+            let tangent = d / len;
+            if rest <= len {
+                return Some((w[0] + tangent * rest, tangent));
             }
-            return;
+            rest -= len;
+            last = Some((w[1], tangent));
         }
+        last
+    }
 
-        if dx == -dy {
-            if dx < 0 {
-                x1 = x2;
-                y1 = y2;
-                dx = -dx;
-            }
-            for i in 0..dx + 1 {
-                canvas.putpixel(x1 + i, y1 - i, color[3]);
-            }
-            return;
-        }
+    pub fn point_at(&self, distance: f32) -> Option<Point2f> {
+        self.sample_at(distance).map(|(point, _)| point)
+    }
 
-        let k = dy as f32 / dx as f32;
-        let mut e: f32 = 0.;
+    pub fn tangent_at(&self, distance: f32) -> Option<Point2f> {
+        self.sample_at(distance).map(|(_, tangent)| tangent)
+    }
 
-        if dx + dy < 0 {
-            std::mem::swap(&mut x1, &mut x2);
-            std::mem::swap(&mut y1, &mut y2);
+    // closest point on the polyline to a query point,
+    // returns the point and its arc length parameter
+    pub fn closest_point(&self, point: Point2f) -> Option<(Point2f, f32)> {
+        if self.vertices.len() == 1 {
+            return Some((self.vertices[0], 0.));
+        }
+        let mut best: Option<(Point2f, f32)> = None;
+        let mut best_dist = f32::INFINITY;
+        let mut walked = 0.;
+        for w in self.vertices.windows(2) {
+            let d = w[1] - w[0];
+            let len = d.norm();
+            let t = if len == 0. {
+                0.
+            } else {
+                ((point - w[0]).dotx(d) / (len * len)).clamp(0., 1.)
+            };
+            let candidate = w[0] + d * t;
+            let dist = (point - candidate).norm();
+            if dist < best_dist {
+                best_dist = dist;
+                best = Some((candidate, walked + len * t));
+            }
+            walked += len;
         }
+        best
+    }
 
-        if k > 0. && k < 1. {
-            let mut py = y1;
-            for px in x1..x2 {
-                canvas.putpixel(px, py, color[3] * (1. - e));
-                canvas.putpixel(px, py + 1, color[3] * e);
-                e += k;
-                if e >= 1. {
-                    py += 1;
-                    e -= 1.;
+    // resample into count vertices evenly spaced by arc length,
+    // first and last vertices are kept
+    pub fn resample(&self, count: usize) -> LineSegs2f {
+        let length = self.length();
+        let params: Vec<f32> = if count < 2 || length == 0. {
+            (0..count.min(self.vertices.len())).map(|i| i as f32).collect()
+        } else {
+            let step = length / (count - 1) as f32;
+            let mut params = Vec::with_capacity(count);
+            let mut iter = self.vertices.windows(2);
+            let mut seg = iter.next().unwrap();
+            let mut seg_index = 0;
+            let mut seg_start = 0.;
+            let mut seg_len = (seg[1] - seg[0]).norm();
+            for i in 0..count - 1 {
+                let target = step * i as f32;
+                while target > seg_start + seg_len {
+                    match iter.next() {
+                        Some(next) => {
+                            seg_start += seg_len;
+                            seg = next;
+                            seg_index += 1;
+                            seg_len = (seg[1] - seg[0]).norm();
+                        }
+                        None => break,
+                    }
                 }
+                let t = if seg_len == 0. {
+                    0.
+                } else {
+                    ((target - seg_start) / seg_len).min(1.)
+                };
+                params.push(seg_index as f32 + t);
             }
-        } else if k > 1. {
-            let mut px = x1;
-            for py in y1..y2 {
-                canvas.putpixel(px, py, color[3] * (1. - e));
-                canvas.putpixel(px + 1, py, color[3] * e);
-                e += 1. / k;
-                if e >= 1. {
-                    px += 1;
-                    e -= 1.;
+            params.push((self.vertices.len() - 1) as f32);
+            params
+        };
+        let vertices = params
+            .iter()
+            .map(|s| point_at_param(&self.vertices, *s))
+            .collect();
+        self.with_params(vertices, &params)
+    }
+
+    // split into the parts inside and outside of a polygon region
+    pub fn clip(&self, region: &Polygon2f) -> (Vec<LineSegs2f>, Vec<LineSegs2f>) {
+        let (inside, outside) =
+            clip::clip_polyline_params(&self.vertices, &region.contours, region.fill_rule);
+        let build = |parts: Vec<clip::ParamPolyline>| {
+            parts
+                .into_iter()
+                .map(|part| {
+                    // colors come from where the cut vertices lie on self,
+                    // not the closest point which is ambiguous where self touches itself
+                    let (vertices, params): (Vec<Point2f>, Vec<f32>) = part.into_iter().unzip();
+                    self.with_params(vertices, &params)
+                })
+                .collect()
+        };
+        (build(inside), build(outside))
+    }
+
+    // draw with whatever color or paint the canvas has,
+    // or the vertex colors if there are
+    pub(crate) fn stroke(&self, canvas: &mut Canvas, alpha: f32) {
+        // supersampled lines keep their width in output pixels,
+        // masks of the canvas too
+        let width = canvas.line_width;
+        // wu touches the pixels next to the line
+        let rect = canvas.visible_rect(1. + width);
+        // a closed polyline joins its last segment to the first
+        let closed = self.vertices.len() > 2 && self.vertices.first() == self.vertices.last();
+        // end column of the last segment, waiting for the next one
+        // to add the coverage of its start
+        let mut pending: Option<WuColumn> = None;
+        let mut first: Option<WuColumn> = None;
+        for (i, w) in self.vertices.windows(2).enumerate() {
+            let (p1, p2) = (w[0] * canvas.scaler, w[1] * canvas.scaler);
+            // clipped so far away vertices do not cost or overflow
+            let (a, b) = match clip::clip_segment_rect(p1, p2, rect) {
+                Some(clipped) => clipped,
+                None => {
+                    if let Some(column) = pending.take() {
+                        column.plot(alpha, canvas);
+                    }
+                    continue;
+                }
+            };
+            let colors = self.vertex_colors.as_ref().map(|colors| {
+                // colors at the clipped ends
+                let d = p2 - p1;
+                let t = |p: Point2f| (p - p1).dotx(d) / d.dotx(d);
+                (
+                    mix_colors(colors[i], colors[i + 1], t(a)),
+                    mix_colors(colors[i], colors[i + 1], t(b)),
+                )
+            });
+            // a start moved by clipping is not the shared vertex
+            if a != p1 {
+                if let Some(column) = pending.take() {
+                    column.plot(alpha, canvas);
                 }
             }
-        } else if k > -1. && k < 0. {
-            let mut py = y1;
-            for px in x1..x2 {
-                canvas.putpixel(px, py, color[3] * (1. + e));
-                canvas.putpixel(px, py - 1, color[3] * -e);
-                e += k;
-                if e <= -1. {
-                    py -= 1;
-                    e += 1.0;
+            let (start, end) = match LineSegs2f::wu(a, b, alpha, colors, width, canvas) {
+                Some(columns) => columns,
+                None => continue,
+            };
+            match start {
+                Some(start) => {
+                    let start = match pending.take() {
+                        Some(column) => column.merge(start),
+                        None => start,
+                    };
+                    if i == 0 && closed && a == p1 {
+                        first = Some(start);
+                    } else {
+                        start.plot(alpha, canvas);
+                    }
+                    pending = Some(end);
+                }
+                // within one column, everything waits for the next segment
+                None => {
+                    pending = Some(match pending.take() {
+                        Some(column) => column.merge(end),
+                        None => end,
+                    });
                 }
             }
-        } else if k < -1. {
-            let mut px = x2;
-            for py in (y1..y2).rev() {
-                canvas.putpixel(px, py, color[3] * (1. - e));
-                canvas.putpixel(px + 1, py, color[3] * e);
-                e += -1. / k;
-                if e >= 1. {
-                    px += 1;
-                    e -= 1.;
+            if b != p2 {
+                if let Some(column) = pending.take() {
+                    column.plot(alpha, canvas);
                 }
             }
         }
+        match (pending, first) {
+            (Some(column), Some(first)) => column.merge(first).plot(alpha, canvas),
+            (Some(column), None) | (None, Some(column)) => column.plot(alpha, canvas),
+            (None, None) => {}
+        }
+    }
+
+    // Xiaolin Wu's line with sub-pixel end points in pixel coordinates,
+    // width pixels across
+    // drawn with the color or paint already set on the canvas,
+    // or colors going from p1 to p2 if given
+    // the columns at p1 and p2 are returned instead of drawn, so
+    // connected segments can draw their shared vertex once with the
+    // coverage of both, the one at p1 is None if both ends share a column
+    fn wu(
+        p1: Point2f,
+        p2: Point2f,
+        alpha: f32,
+        mut colors: Option<([f32; 4], [f32; 4])>,
+        width: f32,
+        canvas: &mut Canvas,
+    ) -> Option<(Option<WuColumn>, WuColumn)> {
+        // pixel centers are at integer coordinates in this function
+        let half = Point2f::from_floats(0.5, 0.5);
+        let (mut p1, mut p2) = (p1 - half, p2 - half);
+        if p1 == p2 {
+            return None;
+        }
+        let steep = (p2.y - p1.y).abs() > (p2.x - p1.x).abs();
+        if steep {
+            std::mem::swap(&mut p1.x, &mut p1.y);
+            std::mem::swap(&mut p2.x, &mut p2.y);
+        }
+        let reversed = p1.x > p2.x;
+        if reversed {
+            std::mem::swap(&mut p1, &mut p2);
+            colors = colors.map(|(c1, c2)| (c2, c1));
+        }
+        let column = |x: f32, y: f32, coverage: f32| WuColumn {
+            x: x as i32,
+            y,
+            coverage,
+            steep,
+            // along the major axis, the same ratio as along the line
+            color: colors.map(|(c1, c2)| {
+                mix_colors(c1, c2, ((x - p1.x) / (p2.x - p1.x)).clamp(0., 1.))
+            }),
+            width,
+        };
+        let gradient = (p2.y - p1.y) / (p2.x - p1.x);
+
+        let x_start = p1.x.round();
+        let x_end = p2.x.round();
+        if x_start == x_end {
+            // both ends in one column, coverage is the length inside
+            return Some((None, column(x_start, (p1.y + p2.y) / 2., p2.x - p1.x)));
+        }
+        // end points are covered by the part of the line in their column
+        let first = column(
+            x_start,
+            p1.y + gradient * (x_start - p1.x),
+            x_start + 0.5 - p1.x,
+        );
+        let last = column(x_end, p2.y + gradient * (x_end - p2.x), p2.x - (x_end - 0.5));
+        let mut y = p1.y + gradient * (x_start + 1. - p1.x);
+        for x in x_start as i32 + 1..x_end as i32 {
+            column(x as f32, y, 1.).plot(alpha, canvas);
+            y += gradient;
+        }
+        Some(if reversed {
+            (Some(last), first)
+        } else {
+            (Some(first), last)
+        })
     }
 }
 
@@ -163,44 +459,220 @@ impl GraphicObject for LineSegs2f {
     }
 
     fn rotate(&self, rotate_mat: Mat2x2f) -> Box<dyn GraphicObject> {
-        Box::new(LineSegs2f {
-            vertices: self.vertices.iter().map(|x| rotate_mat * *x).collect(),
-            color: self.color,
-        })
+        Box::new(self.map_vertices(|x| rotate_mat * x))
     }
 
     fn zoom(&self, k: f32) -> Box<dyn GraphicObject> {
-        Box::new(LineSegs2f {
-            vertices: self.vertices.iter().map(|x| *x * k).collect(),
-            color: self.color,
-        })
+        Box::new(self.map_vertices(|x| x * k))
     }
 
     fn shear(&self, k: f32) -> Box<dyn GraphicObject> {
-        Box::new(LineSegs2f {
-            vertices: self.vertices.iter().map(|x| Point2f::from_floats(x.x + k * x.y, x.y)).collect(),
-            color: self.color,
-        })
+        Box::new(self.map_vertices(|x| Point2f::from_floats(x.x + k * x.y, x.y)))
     }
 
-    fn render(&self, mut canvas: &mut Canvas) {
-        let mut flag = false;
-        let mut x1: f32 = 0.; // convince compiler
-        let mut x2: f32;
-        let mut y1: f32 = 0.; // convince compiler
-        let mut y2: f32;
-        for vertex in self.vertices.iter() {
-            if !flag {
-                flag = true;
-                x1 = vertex.x;
-                y1 = vertex.y;
-            } else {
-                x2 = vertex.x;
-                y2 = vertex.y;
-                LineSegs2f::wu(x1, y1, x2, y2, self.color, &mut canvas);
-                x1 = x2;
-                y1 = y2;
+    fn projective(&self, mat: Mat3x3f, max_len: Option<f32>) -> Box<dyn GraphicObject> {
+        let line_segs = match max_len {
+            Some(max_len) => {
+                let params = subdivide_params(&self.vertices, max_len, false);
+                let vertices = params
+                    .iter()
+                    .map(|s| point_at_param(&self.vertices, *s))
+                    .collect();
+                self.with_params(vertices, &params)
             }
+            None => self.clone(),
+        };
+        Box::new(line_segs.map_vertices(|x| mat * x))
+    }
+
+    fn warp(&self, f: &dyn Fn(Point2f) -> Point2f, tolerance: f32) -> Box<dyn GraphicObject> {
+        let (vertices, params): (Vec<Point2f>, Vec<f32>) =
+            warp_params(&self.vertices, f, tolerance, false)
+                .into_iter()
+                .unzip();
+        Box::new(self.with_params(vertices, &params))
+    }
+
+    fn render(&self, canvas: &mut Canvas) {
+        canvas.set_blend_mode(self.blend_mode);
+        canvas.set_color([self.color[0], self.color[1], self.color[2]]);
+        // vertex colors bring their own alpha
+        let alpha = if self.vertex_colors.is_some() {
+            1.
+        } else {
+            self.color[3]
+        };
+        self.stroke(canvas, alpha);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LineSegs2f;
+    use crate::algebra::{Mat3x3f, Point2f};
+    use crate::canvas::{Canvas, DownsampleFilter};
+    use crate::graphic_object::{GraphicObject, GraphicObjects, Polygon2f};
+
+    #[test]
+    fn test_measurement() {
+        let eps: f32 = 1e-5;
+        // L shape, 3 + 4
+        let line_segs = LineSegs2f::from_floats(vec![
+            1., 1., 1., 1., 0., 0., 3., 0., 3., 4.,
+        ]);
+        assert!((line_segs.length() - 7.).abs() < eps);
+
+        let (point, tangent) = line_segs.sample_at(5.).unwrap();
+        assert!((point - Point2f::from_floats(3., 2.)).norm() < eps);
+        assert!((tangent - Point2f::from_floats(0., 1.)).norm() < eps);
+        // clamped
+        let point = line_segs.point_at(100.).unwrap();
+        assert!((point - Point2f::from_floats(3., 4.)).norm() < eps);
+
+        let (point, t) = line_segs
+            .closest_point(Point2f::from_floats(4., 1.))
+            .unwrap();
+        assert!((point - Point2f::from_floats(3., 1.)).norm() < eps);
+        assert!((t - 4.).abs() < eps);
+
+        let resampled = line_segs.resample(8);
+        assert_eq!(resampled.vertices.len(), 8);
+        for w in resampled.vertices.windows(2) {
+            // the corner is cut
+            assert!((w[1] - w[0]).norm() <= 1. + eps);
+        }
+        assert!((resampled.vertices[3] - Point2f::from_floats(3., 0.)).norm() < eps);
+        assert!((resampled.vertices[7] - Point2f::from_floats(3., 4.)).norm() < eps);
+    }
+
+    #[test]
+    fn test_wu() {
+        let mut canvas = Canvas::new((6, 6), 1.);
+        let line_segs = LineSegs2f::from_floats(vec![
+            1., 1., 1., 1., 0.5, 0.5, 4.5, 0.5, 4.5, 4.5,
+        ]);
+        line_segs.render(&mut canvas);
+        let pixel = |x: usize, y: usize| canvas.data[(y * 6 + x) * 3];
+        assert_eq!(pixel(0, 0), 127);
+        assert_eq!(pixel(2, 0), 255);
+        // the corner is covered by both segments, blended once
+        assert_eq!(pixel(4, 0), 255);
+        assert_eq!(pixel(4, 2), 255);
+        assert_eq!(pixel(4, 4), 127);
+        assert_eq!(pixel(2, 1), 0);
+    }
+
+    #[test]
+    fn test_wu_joints() {
+        let draw = |vertices: Vec<Point2f>| {
+            let mut canvas = Canvas::new((10, 10), 1.);
+            LineSegs2f::new(vertices, [1., 1., 1., 1.]).render(&mut canvas);
+            canvas.data.to_vec()
+        };
+        let p = Point2f::from_floats;
+        // collinear pieces look like the whole line, the joint included
+        let whole = draw(vec![p(0.5, 1.5), p(8.5, 1.5)]);
+        let joined = draw(vec![p(0.5, 1.5), p(3.3, 1.5), p(8.5, 1.5)]);
+        assert_eq!(joined, whole);
+        for x in 1..8 {
+            assert_eq!(joined[(10 + x) * 3], 255, "{}", x);
         }
+
+        // many sub-pixel segments, as from resample, do not leave holes
+        let line = LineSegs2f::new(vec![p(0.5, 1.5), p(8.5, 1.5)], [1.; 4]);
+        let fine = line.resample(100);
+        assert_eq!(draw(fine.vertices), whole);
+        let diagonal = LineSegs2f::new(vec![p(0.5, 0.5), p(8.5, 6.5)], [1.; 4]);
+        let sum = |data: Vec<u8>| data.iter().map(|x| *x as i32).sum::<i32>();
+        let coarse = sum(draw(diagonal.vertices.clone()));
+        let fine = sum(draw(diagonal.resample(100).vertices));
+        assert!((fine - coarse).abs() < coarse / 50, "{} {}", fine, coarse);
+    }
+
+    #[test]
+    fn test_wu_supersampled() {
+        // a diagonal hairline keeps its weight through supersampling
+        let line = LineSegs2f::from_floats(vec![1., 1., 1., 1., 0.5, 1.5, 7.5, 5.]);
+        let sum = |canvas: &Canvas| canvas.data.iter().map(|x| *x as i32).sum::<i32>();
+        let mut canvas = Canvas::new((8, 8), 1.);
+        line.render(&mut canvas);
+        let plain = sum(&canvas);
+        let mut canvas = Canvas::new_supersampled((8, 8), 1., 4, DownsampleFilter::Box);
+        line.render(&mut canvas);
+        canvas.finalize();
+        let supersampled = sum(&canvas);
+        assert!((supersampled - plain).abs() < plain / 20, "{} {}", supersampled, plain);
+    }
+
+    #[test]
+    fn test_vertex_colors() {
+        // red to blue along a horizontal line through pixel centers
+        let line = "l colored 0 0 0 1 0.5 0.5 1 0 0 1 8.5 0.5 0 0 1 1";
+        let graphic_object = GraphicObjects::from_strs(vec![line])
+            .into_iter()
+            .next()
+            .unwrap();
+        let line_segs = graphic_object
+            .as_any()
+            .downcast_ref::<LineSegs2f>()
+            .unwrap();
+        assert_eq!(line_segs.to_str(), line);
+
+        let mut canvas = Canvas::new((10, 2), 1.);
+        line_segs.render(&mut canvas);
+        let pixel = |x: usize, c: usize| canvas.data[x * 3 + c];
+        assert_eq!(pixel(4, 0), 127);
+        assert_eq!(pixel(4, 2), 127);
+        assert_eq!(pixel(6, 0), 63);
+        assert_eq!(pixel(6, 2), 191);
+
+        // new vertices get the colors between
+        let subdivided = line_segs
+            .projective(Mat3x3f::identity(), Some(2.))
+            .as_any()
+            .downcast_ref::<LineSegs2f>()
+            .unwrap()
+            .clone();
+        assert_eq!(subdivided.vertices.len(), 5);
+        assert_eq!(subdivided.vertex_colors.unwrap()[2], [0.5, 0., 0.5, 1.]);
+        let resampled = line_segs.resample(3);
+        assert_eq!(resampled.vertex_colors.unwrap()[1], [0.5, 0., 0.5, 1.]);
+        let warped = line_segs.warp(&|p| Point2f::from_floats(p.x, p.x * p.x / 8.), 0.1);
+        let warped = warped.as_any().downcast_ref::<LineSegs2f>().unwrap();
+        let colors = warped.vertex_colors.as_ref().unwrap();
+        assert!(colors.len() > 2);
+        assert_eq!(colors.len(), warped.vertices.len());
+        assert_eq!(*colors.last().unwrap(), [0., 0., 1., 1.]);
+    }
+
+    #[test]
+    fn test_clip_colors() {
+        // there and back along the same line, red to green to blue
+        let line_segs = LineSegs2f::with_vertex_colors(
+            vec![
+                Point2f::from_floats(0., 0.),
+                Point2f::from_floats(4., 0.),
+                Point2f::from_floats(0., 0.),
+            ],
+            [1.; 4],
+            vec![[1., 0., 0., 1.], [0., 1., 0., 1.], [0., 0., 1., 1.]],
+        );
+        let region = Polygon2f::new(
+            vec![
+                Point2f::from_floats(1., -1.),
+                Point2f::from_floats(3., -1.),
+                Point2f::from_floats(3., 1.),
+                Point2f::from_floats(1., 1.),
+            ],
+            [1.; 4],
+            [0.; 4],
+        );
+        let (inside, outside) = line_segs.clip(&region);
+        assert_eq!(inside.len(), 2);
+        assert_eq!(outside.len(), 3);
+        // the way back keeps its own colors where it passes the same points
+        let colors = |part: &LineSegs2f| part.vertex_colors.clone().unwrap();
+        assert_eq!(colors(&inside[0]), vec![[0.75, 0.25, 0., 1.], [0.25, 0.75, 0., 1.]]);
+        assert_eq!(colors(&inside[1]), vec![[0., 0.75, 0.25, 1.], [0., 0.25, 0.75, 1.]]);
     }
 }
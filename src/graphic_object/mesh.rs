@@ -0,0 +1,221 @@
+use std::any::Any;
+
+use crate::algebra::{Point2f, Mat2x2f, Mat3x3f};
+use crate::blend::BlendMode;
+use crate::canvas::Canvas;
+use super::GraphicObject;
+
+// triangles sharing vertices, colors are interpolated inside of them
+#[derive(Clone, Debug)]
+pub struct TriangleMesh2f {
+    pub vertices: Vec<Point2f>,
+    // rgba of every vertex
+    pub colors: Vec<[f32; 4]>,
+    // counter/clockwise both work
+    pub indices: Vec<[usize; 3]>,
+    pub blend_mode: BlendMode,
+}
+
+// cross(b - a, p - a), computed from the same end of the edge
+// whichever way it is walked, so the two triangles of a shared edge
+// get exactly opposite values
+fn edge_function(a: Point2f, b: Point2f, p: Point2f) -> f32 {
+    if (a.x, a.y) < (b.x, b.y) {
+        (b - a).crossx(p - a)
+    } else {
+        -(a - b).crossx(p - b)
+    }
+}
+
+impl TriangleMesh2f {
+    pub fn new(
+        vertices: Vec<Point2f>,
+        colors: Vec<[f32; 4]>,
+        indices: Vec<[usize; 3]>,
+    ) -> TriangleMesh2f {
+        if vertices.len() != colors.len() {
+            panic!("vertex color count mismatch");
+        }
+        if indices.iter().flatten().any(|x| *x >= vertices.len()) {
+            panic!("index out of range");
+        }
+        TriangleMesh2f {
+            vertices,
+            colors,
+            indices,
+            blend_mode: BlendMode::SourceOver,
+        }
+    }
+
+    fn map_vertices(&self, f: impl Fn(Point2f) -> Point2f) -> TriangleMesh2f {
+        TriangleMesh2f {
+            vertices: self.vertices.iter().map(|x| f(*x)).collect(),
+            colors: self.colors.clone(),
+            indices: self.indices.clone(),
+            blend_mode: self.blend_mode,
+        }
+    }
+
+    // pixel centers strictly inside are drawn, those exactly on an edge
+    // only for top and left edges, so a pixel is never drawn twice
+    // or skipped along a shared edge
+    fn render_triangle(p: [Point2f; 3], colors: [[f32; 4]; 3], canvas: &mut Canvas) {
+        let area = edge_function(p[0], p[1], p[2]);
+        if area == 0. {
+            return;
+        }
+        let sign = area.signum();
+        // edge i is opposite to vertex i
+        let edges = [(p[1], p[2]), (p[2], p[0]), (p[0], p[1])];
+        let mut tie_inside = [false; 3];
+        for (tie, (a, b)) in tie_inside.iter_mut().zip(edges.iter()) {
+            let d = *b - *a;
+            // normal pointing inside, pixel coordinates are y-down
+            let normal = Point2f::from_floats(-d.y, d.x) * sign;
+            *tie = if d.y == 0. { normal.y > 0. } else { normal.x > 0. };
+        }
+        let (clip_x0, clip_y0, clip_x1, clip_y1) = canvas.get_clip();
+        let min_x = p.iter().fold(f32::INFINITY, |m, x| m.min(x.x));
+        let max_x = p.iter().fold(f32::NEG_INFINITY, |m, x| m.max(x.x));
+        let min_y = p.iter().fold(f32::INFINITY, |m, x| m.min(x.y));
+        let max_y = p.iter().fold(f32::NEG_INFINITY, |m, x| m.max(x.y));
+        let x0 = (min_x.floor() as i32).max(clip_x0);
+        let x1 = (max_x.ceil() as i32).min(clip_x1);
+        let y0 = (min_y.floor() as i32).max(clip_y0);
+        let y1 = (max_y.ceil() as i32).min(clip_y1);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let center = Point2f::from_floats(x as f32 + 0.5, y as f32 + 0.5);
+                let mut weights = [0f32; 3];
+                let mut inside = true;
+                for (i, (a, b)) in edges.iter().enumerate() {
+                    let w = edge_function(*a, *b, center) * sign;
+                    if w < 0. || (w == 0. && !tie_inside[i]) {
+                        inside = false;
+                        break;
+                    }
+                    weights[i] = w / (area * sign);
+                }
+                if !inside {
+                    continue;
+                }
+                // premultiplied, so transparent vertices do not darken
+                let mut color = [0f32; 4];
+                for (vertex_color, w) in colors.iter().zip(weights.iter()) {
+                    let k = vertex_color[3] * w;
+                    for (c, v) in color.iter_mut().zip(vertex_color.iter()).take(3) {
+                        *c += v * k;
+                    }
+                    color[3] += k;
+                }
+                if color[3] <= 0. {
+                    continue;
+                }
+                canvas.set_color([
+                    color[0] / color[3],
+                    color[1] / color[3],
+                    color[2] / color[3],
+                ]);
+                canvas.putpixel(x, y, color[3]);
+            }
+        }
+    }
+}
+
+impl GraphicObject for TriangleMesh2f {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn shift(&self, dp: Point2f) -> Box<dyn GraphicObject> {
+        Box::new(self.map_vertices(|x| x + dp))
+    }
+
+    fn rotate(&self, rotate_mat: Mat2x2f) -> Box<dyn GraphicObject> {
+        Box::new(self.map_vertices(|x| rotate_mat * x))
+    }
+
+    fn zoom(&self, k: f32) -> Box<dyn GraphicObject> {
+        Box::new(self.map_vertices(|x| x * k))
+    }
+
+    fn shear(&self, k: f32) -> Box<dyn GraphicObject> {
+        Box::new(self.map_vertices(|x| Point2f::from_floats(x.x + k * x.y, x.y)))
+    }
+
+    // triangles stay triangles, splitting them would break shared edges
+    fn projective(&self, mat: Mat3x3f, _max_len: Option<f32>) -> Box<dyn GraphicObject> {
+        Box::new(self.map_vertices(|x| mat * x))
+    }
+
+    // only vertices are mapped, the mesh should be fine enough
+    fn warp(&self, f: &dyn Fn(Point2f) -> Point2f, _tolerance: f32) -> Box<dyn GraphicObject> {
+        Box::new(self.map_vertices(f))
+    }
+
+    fn render(&self, canvas: &mut Canvas) {
+        canvas.set_blend_mode(self.blend_mode);
+        let vertices: Vec<Point2f> = self.vertices.iter().map(|x| *x * canvas.scaler).collect();
+        for index in self.indices.iter() {
+            TriangleMesh2f::render_triangle(
+                [vertices[index[0]], vertices[index[1]], vertices[index[2]]],
+                [
+                    self.colors[index[0]],
+                    self.colors[index[1]],
+                    self.colors[index[2]],
+                ],
+                canvas,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TriangleMesh2f;
+    use crate::algebra::Point2f;
+    use crate::canvas::Canvas;
+    use crate::graphic_object::GraphicObject;
+
+    #[test]
+    fn test_mesh() {
+        // a fan around the center of a square, all edges through pixel centers
+        let vertices = vec![
+            Point2f::from_floats(0.5, 0.5),
+            Point2f::from_floats(6.5, 0.5),
+            Point2f::from_floats(6.5, 6.5),
+            Point2f::from_floats(0.5, 6.5),
+            Point2f::from_floats(3.5, 3.5),
+        ];
+        let colors = vec![[1., 1., 1., 0.5]; 5];
+        let indices = vec![[0, 1, 4], [1, 2, 4], [4, 2, 3], [3, 0, 4]];
+        let mesh = TriangleMesh2f::new(vertices.clone(), colors, indices.clone());
+        let mut canvas = Canvas::new((8, 8), 1.);
+        mesh.render(&mut canvas);
+        // every pixel inside drawn exactly once, the top-left edges included
+        for y in 0..8 {
+            for x in 0..8 {
+                let inside = x < 6 && y < 6;
+                let expected = if inside { 127 } else { 0 };
+                assert_eq!(canvas.data[(y * 8 + x) * 3], expected, "{} {}", x, y);
+            }
+        }
+
+        // interpolated from the corners
+        let colors = vec![
+            [1., 0., 0., 1.],
+            [1., 0., 0., 1.],
+            [0., 0., 1., 1.],
+            [0., 0., 1., 1.],
+            [0.5, 0., 0.5, 1.],
+        ];
+        let mesh = TriangleMesh2f::new(vertices, colors, indices);
+        let mut canvas = Canvas::new((8, 8), 1.);
+        mesh.shift(Point2f::from_floats(0.5, 0.)).render(&mut canvas);
+        let pixel = |x: usize, y: usize, c: usize| canvas.data[(y * 8 + x) * 3 + c];
+        assert_eq!(pixel(3, 0, 0), 255);
+        assert_eq!(pixel(3, 3, 0), 127);
+        assert_eq!(pixel(3, 3, 2), 127);
+        assert_eq!(pixel(3, 5, 2), 212);
+    }
+}
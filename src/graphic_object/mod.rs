@@ -1,13 +1,22 @@
-use crate::algebra::{Mat2x2f, Point2f};
+use crate::algebra::{Mat2x2f, Mat3x3f, Point2f};
+use crate::blend::BlendMode;
 use crate::canvas::Canvas;
+use crate::paint::{mix_colors, Paint};
 use std::any::Any;
 
 use dyn_clone::DynClone;
 
+pub mod arc;
 pub mod line_segs;
+pub mod mesh;
 pub mod polygon;
+pub mod sdf;
+pub use arc::{Arc2f, ArcMode, EllipticArc2f};
 pub use line_segs::LineSegs2f;
+pub use mesh::TriangleMesh2f;
 pub use polygon::Polygon2f;
+pub use sdf::{Sdf2f, SdfShape};
+pub use crate::rasterizer::FillRule;
 
 pub trait GraphicObject: DynClone + Sync + Send + Any {
     fn as_any(&self) -> &dyn Any;
@@ -15,6 +24,16 @@ pub trait GraphicObject: DynClone + Sync + Send + Any {
     fn rotate(&self, rotate_mat: Mat2x2f) -> Box<dyn GraphicObject>;
     fn zoom(&self, k: f32) -> Box<dyn GraphicObject>;
     fn shear(&self, k: f32) -> Box<dyn GraphicObject>;
+    // edges longer than max_len are subdivided before mapping
+    // objects not mapping themselves panic
+    fn projective(&self, _mat: Mat3x3f, _max_len: Option<f32>) -> Box<dyn GraphicObject> {
+        panic!("projective not supported");
+    }
+    // non-linear map, edges are subdivided until within tolerance
+    // objects not mapping themselves panic
+    fn warp(&self, _f: &dyn Fn(Point2f) -> Point2f, _tolerance: f32) -> Box<dyn GraphicObject> {
+        panic!("warp not supported");
+    }
 
     fn render(&self, canvas: &mut Canvas);
 }
@@ -24,7 +43,8 @@ dyn_clone::clone_trait_object!(GraphicObject);
 // works for both counter/clockwise direction
 pub fn generate_arc_vertices(center: Point2f, r: f32, theta: (f32, f32)) -> Vec<Point2f> {
     const SPLIT_R_K: f32 = 1.; // points every pixel length of arc
-    let split: u32 = ((theta.1 - theta.0).abs() * SPLIT_R_K * r) as u32;
+    // at least one segment for tiny arcs
+    let split: u32 = (((theta.1 - theta.0).abs() * SPLIT_R_K * r) as u32).max(1);
     let d_theta: f32 = (theta.1 - theta.0) / split as f32;
     let mut theta_now = theta.0;
     let mut vertices: Vec<Point2f> = Vec::new();
@@ -35,6 +55,145 @@ pub fn generate_arc_vertices(center: Point2f, r: f32, theta: (f32, f32)) -> Vec<
     vertices
 }
 
+// split edges longer than max_len into equal pieces
+// closed also splits the edge from the last vertex to the first
+pub fn subdivide_vertices(vertices: &[Point2f], max_len: f32, closed: bool) -> Vec<Point2f> {
+    subdivide_params(vertices, max_len, closed)
+        .into_iter()
+        .map(|s| point_at_param(vertices, s))
+        .collect()
+}
+
+// positions of the subdivided vertices as i + t, t along edge i
+pub(crate) fn subdivide_params(vertices: &[Point2f], max_len: f32, closed: bool) -> Vec<f32> {
+    let mut result: Vec<f32> = Vec::new();
+    let len = vertices.len();
+    if len == 0 {
+        return result;
+    }
+    let edge_count = if closed { len } else { len - 1 };
+    for i in 0..edge_count {
+        let a = vertices[i];
+        let b = vertices[(i + 1) % len];
+        // max_len <= 0 or not finite means no subdivision
+        let pieces = (b - a).norm() / max_len;
+        let split = if max_len > 0. && pieces.is_finite() {
+            pieces.ceil().max(1.) as u32
+        } else {
+            1
+        };
+        for j in 0..split {
+            result.push(i as f32 + j as f32 / split as f32);
+        }
+    }
+    if !closed {
+        result.push((len - 1) as f32);
+    }
+    result
+}
+
+pub(crate) fn point_at_param(vertices: &[Point2f], s: f32) -> Point2f {
+    let i = s.floor() as usize;
+    let t = s - i as f32;
+    if t == 0. {
+        return vertices[i];
+    }
+    let a = vertices[i];
+    let b = vertices[(i + 1) % vertices.len()];
+    a + (b - a) * t
+}
+
+// per-vertex colors at positions from subdivide_params or warp_params
+pub(crate) fn interpolate_colors(colors: &[[f32; 4]], params: &[f32]) -> Vec<[f32; 4]> {
+    params
+        .iter()
+        .map(|s| {
+            let i = s.floor() as usize;
+            let t = s - i as f32;
+            if t == 0. {
+                return colors[i];
+            }
+            mix_colors(colors[i], colors[(i + 1) % colors.len()], t)
+        })
+        .collect()
+}
+
+// recursion limit of warp_vertices, 2^10 pieces per edge at most
+const MAX_WARP_DEPTH: u32 = 10;
+
+// a and b are (parameter, point), fa and fb the warped points
+fn warp_edge(
+    f: &dyn Fn(Point2f) -> Point2f,
+    ((sa, a), fa): ((f32, Point2f), Point2f),
+    ((sb, b), fb): ((f32, Point2f), Point2f),
+    tolerance: f32,
+    depth: u32,
+    result: &mut Vec<(Point2f, f32)>,
+) {
+    let m = (a + b) / 2.;
+    let sm = (sa + sb) / 2.;
+    let fm = f(m);
+    // deviation of the warped quarter points and midpoint from the chord,
+    // the midpoint alone misses waves crossing the chord there
+    let flat = (fm - (fa + fb) / 2.).norm() <= tolerance
+        && [0.25, 0.75].iter().all(|t| {
+            let on_chord = fa + (fb - fa) * *t;
+            (f(a + (b - a) * *t) - on_chord).norm() <= tolerance
+        });
+    if depth >= MAX_WARP_DEPTH || flat {
+        result.push((fa, sa));
+        return;
+    }
+    warp_edge(f, ((sa, a), fa), ((sm, m), fm), tolerance, depth + 1, result);
+    warp_edge(f, ((sm, m), fm), ((sb, b), fb), tolerance, depth + 1, result);
+}
+
+// apply f to vertices, edges are adaptively subdivided
+// until the warped quarter points are within tolerance
+pub fn warp_vertices(
+    vertices: &[Point2f],
+    f: &dyn Fn(Point2f) -> Point2f,
+    tolerance: f32,
+    closed: bool,
+) -> Vec<Point2f> {
+    warp_params(vertices, f, tolerance, closed)
+        .into_iter()
+        .map(|x| x.0)
+        .collect()
+}
+
+// warp_vertices with the position of every vertex as in subdivide_params
+pub(crate) fn warp_params(
+    vertices: &[Point2f],
+    f: &dyn Fn(Point2f) -> Point2f,
+    tolerance: f32,
+    closed: bool,
+) -> Vec<(Point2f, f32)> {
+    let mut result: Vec<(Point2f, f32)> = Vec::new();
+    let len = vertices.len();
+    if len == 0 {
+        return result;
+    }
+    let warped: Vec<Point2f> = vertices.iter().map(|x| f(*x)).collect();
+    let edge_count = if closed { len } else { len - 1 };
+    for i in 0..edge_count {
+        let j = (i + 1) % len;
+        warp_edge(
+            f,
+            ((i as f32, vertices[i]), warped[i]),
+            // the end of edge i, not the start of edge j
+            (((i + 1) as f32, vertices[j]), warped[j]),
+            tolerance,
+            0,
+            &mut result,
+        );
+    }
+    if !closed {
+        result.push((warped[len - 1], (len - 1) as f32));
+    }
+    result
+}
+
 pub fn generate_thick_arc(
     center: Point2f,
     r: (f32, f32),
@@ -46,18 +205,15 @@ pub fn generate_thick_arc(
     nodes.extend(generate_arc_vertices(center, r.1, (theta.1, theta.0)));
     let mut graphic_objects: GraphicObjects = Default::default();
     if let Some(fill_color) = fill_color {
-        graphic_objects.push(Box::new(Polygon2f {
-            vertices: nodes.clone(),
-            color: fill_color,
-            border_color: [0., 0., 0., 0.],
-        }));
+        graphic_objects.push(Box::new(Polygon2f::new(
+            nodes.clone(),
+            fill_color,
+            [0., 0., 0., 0.],
+        )));
     }
     nodes.push(nodes[0]);
     if let Some(border_color) = border_color {
-        graphic_objects.push(Box::new(LineSegs2f {
-            vertices: nodes,
-            color: border_color,
-        }));
+        graphic_objects.push(Box::new(LineSegs2f::new(nodes, border_color)));
     }
     graphic_objects
 }
@@ -122,6 +278,26 @@ impl GraphicObjects {
         }
     }
 
+    pub fn projective(&self, mat: Mat3x3f, max_len: Option<f32>) -> GraphicObjects {
+        GraphicObjects {
+            graphic_objects: self
+                .graphic_objects
+                .iter()
+                .map(|graphic_object| graphic_object.projective(mat, max_len))
+                .collect(),
+        }
+    }
+
+    pub fn warp(&self, f: &dyn Fn(Point2f) -> Point2f, tolerance: f32) -> GraphicObjects {
+        GraphicObjects {
+            graphic_objects: self
+                .graphic_objects
+                .iter()
+                .map(|graphic_object| graphic_object.warp(f, tolerance))
+                .collect(),
+        }
+    }
+
     pub fn push(&mut self, element: Box<dyn GraphicObject>) {
         self.graphic_objects.push(element);
     }
@@ -130,6 +306,19 @@ impl GraphicObjects {
         self.graphic_objects.extend(other.graphic_objects);
     }
 
+    // in the order pushed, into_iter goes the other way
+    pub fn iter(&self) -> std::slice::Iter<'_, Box<dyn GraphicObject>> {
+        self.graphic_objects.iter()
+    }
+
+    // one object per line:
+    // l [blend <mode>] r g b a x y ...
+    // l [blend <mode>] colored r g b a x y r g b a ...
+    // p [evenodd|nonzero] [blend <mode>] [fill <paint>] [border <paint>] r g b a x y ...
+    //   [| x y ...]
+    // P [evenodd|nonzero] [blend <mode>] [fill <paint>] [border <paint>] br bg bb ba
+    //   r g b a x y ... [| x y ...]
+    // <paint> is described in Paint::from_tokens, <mode> in BlendMode::parse
     pub fn from_strs(strings: Vec<&str>) -> GraphicObjects {
         let mut graphic_objects = GraphicObjects {
             graphic_objects: Vec::new(),
@@ -137,30 +326,90 @@ impl GraphicObjects {
         for line in strings.iter() {
             let splited = line.split_whitespace().collect::<Vec<&str>>();
             match splited[0] {
-                "l" => graphic_objects
-                    .graphic_objects
-                    .push(Box::new(LineSegs2f::from_floats(
-                        splited[1..]
+                "l" => {
+                    let mut numbers = &splited[1..];
+                    let mut blend_mode = BlendMode::SourceOver;
+                    if numbers.first() == Some(&"blend") {
+                        blend_mode = BlendMode::parse(numbers.get(1).expect("Format error"));
+                        numbers = &numbers[2..];
+                    }
+                    // per-vertex colors follow every x y
+                    let colored = numbers.first() == Some(&"colored");
+                    let floats: Vec<f32> = numbers[if colored { 1 } else { 0 }..]
+                        .iter()
+                        .map(|x| x.parse::<f32>().expect("float parse fail"))
+                        .collect();
+                    let mut line_segs = if colored {
+                        LineSegs2f::from_colored_floats(floats)
+                    } else {
+                        LineSegs2f::from_floats(floats)
+                    };
+                    line_segs.blend_mode = blend_mode;
+                    graphic_objects.graphic_objects.push(Box::new(line_segs))
+                }
+                "p" | "P" => {
+                    // optional keywords before the numbers
+                    let mut fill_rule = FillRule::EvenOdd;
+                    let mut blend_mode = BlendMode::SourceOver;
+                    let mut paint: Option<Paint> = None;
+                    let mut border_paint: Option<Paint> = None;
+                    let mut numbers = &splited[1..];
+                    loop {
+                        match numbers.first() {
+                            Some(&"evenodd") => fill_rule = FillRule::EvenOdd,
+                            Some(&"nonzero") => fill_rule = FillRule::NonZero,
+                            Some(&"blend") => {
+                                let mode = numbers.get(1).expect("Format error");
+                                blend_mode = BlendMode::parse(mode);
+                                numbers = &numbers[1..];
+                            }
+                            Some(&"fill") | Some(&"border") => {
+                                let (parsed, used) = Paint::from_tokens(&numbers[1..]);
+                                if numbers[0] == "fill" {
+                                    paint = Some(parsed);
+                                } else {
+                                    border_paint = Some(parsed);
+                                }
+                                numbers = &numbers[used..];
+                            }
+                            _ => break,
+                        }
+                        numbers = &numbers[1..];
+                    }
+                    let parse = |tokens: &[&str]| {
+                        tokens
                             .iter()
                             .map(|x| x.parse::<f32>().expect("float parse fail"))
-                            .collect(),
-                    ))),
-                "p" => graphic_objects
-                    .graphic_objects
-                    .push(Box::new(Polygon2f::from_floats(
-                        vec![0f32; 4].into_iter().chain(splited[1..]
-                            .iter()
-                            .map(|x| x.parse::<f32>().expect("float parse fail")))
-                            .collect(),
-                    ))),
-                "P" => graphic_objects
-                    .graphic_objects
-                    .push(Box::new(Polygon2f::from_floats(
-                        splited[1..]
-                            .iter()
-                            .map(|x| x.parse::<f32>().expect("float parse fail"))
-                            .collect(),
-                    ))),
+                            .collect::<Vec<f32>>()
+                    };
+                    // contours are separated by "|"
+                    let mut groups = numbers.split(|x| *x == "|");
+                    let floats = parse(groups.next().unwrap());
+                    // "p" has no border color
+                    let floats: Vec<f32> = if splited[0] == "p" {
+                        vec![0f32; 4].into_iter().chain(floats).collect()
+                    } else {
+                        floats
+                    };
+                    let mut polygon2f = Polygon2f::from_floats(floats);
+                    polygon2f.fill_rule = fill_rule;
+                    polygon2f.paint = paint;
+                    polygon2f.border_paint = border_paint;
+                    polygon2f.blend_mode = blend_mode;
+                    for group in groups {
+                        let floats = parse(group);
+                        if floats.len() % 2 != 0 {
+                            panic!("odd parse");
+                        }
+                        polygon2f.contours.push(
+                            floats
+                                .chunks(2)
+                                .map(|x| Point2f::from_floats(x[0], x[1]))
+                                .collect(),
+                        );
+                    }
+                    graphic_objects.graphic_objects.push(Box::new(polygon2f))
+                }
                 _ => panic!("Format error"),
             }
         }
@@ -190,3 +439,92 @@ impl Iterator for GraphicObjectsIntoIter {
         self.graphic_objects.graphic_objects.pop()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{warp_params, warp_vertices, GraphicObject};
+    use crate::algebra::{Mat2x2f, Mat3x3f, Point2f};
+    use crate::canvas::Canvas;
+    use std::any::Any;
+
+    // implements only what GraphicObject asked for before projective maps
+    #[derive(Clone)]
+    struct Dot;
+
+    impl GraphicObject for Dot {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn shift(&self, _dp: Point2f) -> Box<dyn GraphicObject> {
+            Box::new(Dot)
+        }
+
+        fn rotate(&self, _rotate_mat: Mat2x2f) -> Box<dyn GraphicObject> {
+            Box::new(Dot)
+        }
+
+        fn zoom(&self, _k: f32) -> Box<dyn GraphicObject> {
+            Box::new(Dot)
+        }
+
+        fn shear(&self, _k: f32) -> Box<dyn GraphicObject> {
+            Box::new(Dot)
+        }
+
+        fn render(&self, canvas: &mut Canvas) {
+            canvas.putpixel(0, 0, 1.);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "projective not supported")]
+    fn test_projective_default() {
+        let dot: Box<dyn GraphicObject> = Box::new(Dot);
+        dot.render(&mut Canvas::new((1, 1), 1.));
+        dot.projective(Mat3x3f::identity(), None);
+    }
+
+    #[test]
+    fn test_warp() {
+        // one period of a sine over the edge, on the chord at the midpoint
+        let wave = |p: Point2f| {
+            Point2f::from_floats(p.x, p.y + 0.1 * (p.x * std::f32::consts::PI * 2.).sin())
+        };
+        let edge = vec![Point2f::from_floats(0., 0.), Point2f::from_floats(1., 0.)];
+        let mut last_count = 0;
+        for tolerance in [0.01, 0.001].iter() {
+            let warped = warp_vertices(&edge, &wave, *tolerance, false);
+            assert!(warped.len() > last_count.max(2));
+            last_count = warped.len();
+            // the polyline stays close to the curve everywhere
+            for k in 0..=100 {
+                let x = k as f32 / 100.;
+                let w = warped.windows(2).find(|w| w[1].x >= x).unwrap();
+                let y = w[0].y + (w[1].y - w[0].y) * (x - w[0].x) / (w[1].x - w[0].x);
+                assert!((y - wave(Point2f::from_floats(x, 0.)).y).abs() <= 2. * tolerance);
+            }
+        }
+
+        // never flat, stops at the depth limit
+        let warped = warp_vertices(&edge, &wave, 0., false);
+        assert_eq!(warped.len(), (1 << 10) + 1);
+
+        // closed rings are warped along the closing edge, not repeated
+        let square = vec![
+            Point2f::from_floats(0., 0.),
+            Point2f::from_floats(1., 0.),
+            Point2f::from_floats(1., 1.),
+            Point2f::from_floats(0., 1.),
+        ];
+        let swap = |p: Point2f| Point2f::from_floats(p.y, p.x);
+        let bulge = |p: Point2f| Point2f::from_floats(p.x + p.y * (1. - p.y), p.y);
+        let params = warp_params(&square, &bulge, 0.01, true);
+        assert_eq!(params[0], (Point2f::new(), 0.));
+        assert!(params.windows(2).all(|w| w[0].1 < w[1].1));
+        let last = params.last().unwrap().1;
+        assert!(last > 3. && last < 4.);
+        // a linear map needs no subdivision
+        assert_eq!(warp_vertices(&square, &swap, 0.01, true).len(), 4);
+    }
+}
@@ -1,14 +1,27 @@
 use std::any::Any;
 
-use crate::algebra::{Point2f, Mat2x2f};
+use crate::algebra::{Point2f, Mat2x2f, Mat3x3f};
+use crate::blend::BlendMode;
 use crate::canvas::Canvas;
-use super::{LineSegs2f, GraphicObject};
+use crate::clip;
+use crate::paint::Paint;
+use crate::rasterizer::{self, FillRule};
+use super::{subdivide_vertices, warp_vertices, LineSegs2f, GraphicObject};
 
+// every contour is a closed ring, all of them are filled together
 #[derive(Clone, Debug)]
 pub struct Polygon2f {
-    pub vertices: Vec<Point2f>,
+    pub contours: Vec<Vec<Point2f>>,
     pub color: [f32; 4],
     pub border_color: [f32; 4],
+    // exact coverage anti-aliasing, None follows the canvas
+    pub antialias: Option<bool>,
+    pub fill_rule: FillRule,
+    // replace color and border_color if set
+    pub paint: Option<Paint>,
+    pub border_paint: Option<Paint>,
+    // for both fill and border
+    pub blend_mode: BlendMode,
 }
 
 impl GraphicObject for Polygon2f {
@@ -17,170 +30,262 @@ impl GraphicObject for Polygon2f {
     }
 
     fn shift(&self, dp: Point2f) -> Box<dyn GraphicObject> {
-        Box::new(Polygon2f {
-            vertices: self.vertices.iter().map(|x| *x + dp).collect(),
-            color: self.color,
-            border_color: self.border_color,
-        })
+        let mat = Mat2x2f {
+            x1: 1.,
+            x2: 0.,
+            y1: 0.,
+            y2: 1.,
+        };
+        Box::new(
+            self.map_vertices(|x| x + dp)
+                .transform_paints(Mat3x3f::from_affine(mat, dp)),
+        )
     }
 
     fn rotate(&self, rotate_mat: Mat2x2f) -> Box<dyn GraphicObject> {
-        Box::new(Polygon2f {
-            vertices: self.vertices.iter().map(|x| rotate_mat * *x).collect(),
-            color: self.color,
-            border_color: self.border_color,
-        })
+        Box::new(
+            self.map_vertices(|x| rotate_mat * x)
+                .transform_paints(Mat3x3f::from_affine(rotate_mat, Point2f::new())),
+        )
     }
 
     fn zoom(&self, k: f32) -> Box<dyn GraphicObject> {
-        Box::new(Polygon2f {
-            vertices: self.vertices.iter().map(|x| *x * k).collect(),
-            color: self.color,
-            border_color: self.border_color,
-        })
+        let mat = Mat2x2f {
+            x1: k,
+            x2: 0.,
+            y1: 0.,
+            y2: k,
+        };
+        Box::new(
+            self.map_vertices(|x| x * k)
+                .transform_paints(Mat3x3f::from_affine(mat, Point2f::new())),
+        )
     }
 
     fn shear(&self, k: f32) -> Box<dyn GraphicObject> {
-        Box::new(Polygon2f {
-            vertices: self.vertices.iter().map(|x| Point2f::from_floats(x.x + k * x.y, x.y)).collect(),
-            color: self.color,
-            border_color: self.border_color,
-        })
+        let mat = Mat2x2f {
+            x1: 1.,
+            x2: k,
+            y1: 0.,
+            y2: 1.,
+        };
+        Box::new(
+            self.map_vertices(|x| Point2f::from_floats(x.x + k * x.y, x.y))
+                .transform_paints(Mat3x3f::from_affine(mat, Point2f::new())),
+        )
+    }
+
+    fn projective(&self, mat: Mat3x3f, max_len: Option<f32>) -> Box<dyn GraphicObject> {
+        let polygon2f = match max_len {
+            Some(max_len) => self.with_contours(
+                self.contours
+                    .iter()
+                    .map(|contour| subdivide_vertices(contour, max_len, true))
+                    .collect(),
+            ),
+            None => self.clone(),
+        };
+        Box::new(polygon2f.map_vertices(|x| mat * x).transform_paints(mat))
+    }
+
+    // paints are left as they are, they have no non-linear form
+    fn warp(&self, f: &dyn Fn(Point2f) -> Point2f, tolerance: f32) -> Box<dyn GraphicObject> {
+        Box::new(
+            self.with_contours(
+                self.contours
+                    .iter()
+                    .map(|contour| warp_vertices(contour, f, tolerance, true))
+                    .collect(),
+            ),
+        )
     }
 
     fn render(&self, canvas: &mut Canvas) {
+        canvas.set_blend_mode(self.blend_mode);
         canvas.set_color([self.color[0], self.color[1], self.color[2]]);
-        if self.vertices.len() < 3 {
+        // the paint brings its own alpha
+        let alpha = match &self.paint {
+            Some(paint) => {
+                canvas.set_paint(paint);
+                1.
+            }
+            None => self.color[3],
+        };
+        if self.contours.iter().all(|contour| contour.len() < 3) {
             return;
         }
-        #[derive(Debug)]
-        struct Edge {
-            pub startx: i32,
-            pub starty: i32,
-            pub endx: i32,
-            pub endy: i32,
-            pub dxy: f32,
-            pub current_x: f32,
-        }
-        let mut edges: Vec<Edge> = Vec::new();
-        let last_vertex = self.vertices.last().unwrap();
-        let mut last_vertex = (
-            (last_vertex.x * canvas.scaler) as i32,
-            (last_vertex.y * canvas.scaler) as i32,
-        );
-        for vertex in self.vertices.iter() {
-            let vertex_i32 = (
-                (vertex.x * canvas.scaler) as i32,
-                (vertex.y * canvas.scaler) as i32,
-            );
-            // dy = 0 is thrown
-            if vertex_i32.1 > last_vertex.1 {
-                edges.push(Edge {
-                    startx: last_vertex.0,
-                    starty: last_vertex.1,
-                    endx: vertex_i32.0,
-                    endy: vertex_i32.1,
-                    dxy: (vertex_i32.0 - last_vertex.0) as f32
-                        / (vertex_i32.1 - last_vertex.1) as f32,
-                    current_x: last_vertex.0 as f32,
-                })
-            } else {
-                edges.push(Edge {
-                    startx: vertex_i32.0,
-                    starty: vertex_i32.1,
-                    endx: last_vertex.0,
-                    endy: last_vertex.1,
-                    dxy: (vertex_i32.0 - last_vertex.0) as f32
-                        / (vertex_i32.1 - last_vertex.1) as f32,
-                    current_x: vertex_i32.0 as f32,
-                })
-            }
-            last_vertex = vertex_i32;
-        }
-
-        // from big to small, for pop_back
-        edges.sort_by(|x, y| y.starty.partial_cmp(&x.starty).unwrap());
-        let mut pop_yend_list = edges.iter().map(|x| x.endy).collect::<Vec<i32>>();
-        pop_yend_list.sort();
-        let mut pop_p: usize = 0;
-        // should use balanced tree for massive points
-        let mut sorted_processing_edges: Vec<Edge> = Vec::new();
-        let mut current_y = edges.last().unwrap().starty;
-        loop {
-            // debug checkpoint
-            // if sorted_processing_edges.len() %2 != 0 {
-            //     panic!("Odd processing edges!");
-            // }
-
-            let mut need_resort_flag = false;
-            // push
-            while !edges.is_empty() && edges.last().unwrap().starty == current_y {
-                sorted_processing_edges.push(edges.pop().unwrap());
-                need_resort_flag = true;
-            }
-
-            // pops do not need re-sort
-            while pop_p < pop_yend_list.len() && pop_yend_list[pop_p] == current_y {
-                sorted_processing_edges.retain(|x| x.endy != current_y);
-                pop_p += 1;
-            }
+        // clipped so far away vertices do not cost or overflow
+        let rect = canvas.visible_rect(1.);
+        let contours: Vec<Vec<Point2f>> = self
+            .contours
+            .iter()
+            .map(|contour| {
+                let contour: Vec<Point2f> = contour.iter().map(|x| *x * canvas.scaler).collect();
+                clip::clip_rect(&contour, rect)
+            })
+            .collect();
+        if self.antialias.unwrap_or(canvas.antialias) {
+            rasterizer::fill_coverage(canvas, &contours, self.fill_rule, alpha);
+        } else {
+            rasterizer::fill_scanline(canvas, &contours, self.fill_rule, alpha);
+        }
+        self.render_border(canvas);
+    }
+}
 
-            // exit immediately after pop
-            if sorted_processing_edges.is_empty() {
-                break;
-            }
+impl Polygon2f {
+    pub fn new(vertices: Vec<Point2f>, color: [f32; 4], border_color: [f32; 4]) -> Polygon2f {
+        Polygon2f::from_contours(vec![vertices], color, border_color)
+    }
 
-            if need_resort_flag {
-                sorted_processing_edges.sort_by(|x, y| {
-                    x.current_x
-                        .partial_cmp(&y.current_x)
-                        .unwrap()
-                        .then(x.endx.partial_cmp(&y.endx).unwrap())
-                });
-            }
+    pub fn from_contours(
+        contours: Vec<Vec<Point2f>>,
+        color: [f32; 4],
+        border_color: [f32; 4],
+    ) -> Polygon2f {
+        Polygon2f {
+            contours,
+            color,
+            border_color,
+            antialias: None,
+            fill_rule: FillRule::EvenOdd,
+            paint: None,
+            border_paint: None,
+            blend_mode: BlendMode::SourceOver,
+        }
+    }
 
-            let mut draw_on = false;
-            let mut iter = sorted_processing_edges.iter_mut();
-            let mut last_x: i32;
-            {
-                let mut first_edge = iter.next().unwrap();
-                last_x = first_edge.current_x as i32;
-                first_edge.current_x += first_edge.dxy;
-            }
-            for each_processing_edge in iter {
-                draw_on = !draw_on;
-                if draw_on {
-                    let current_x = each_processing_edge.current_x as i32;
-                    // debug checkpoint
-                    // if last_x > current_x {
-                    //     println!("{:?}", sorted_processing_edges);
-                    //     panic!("not sorted!");
-                    // }
-                    for x in last_x + 1..current_x + 1 {
-                        canvas.putpixel(x, current_y, self.color[3]);
-                    }
-                }
-                last_x = each_processing_edge.current_x as i32;
-                each_processing_edge.current_x += each_processing_edge.dxy;
+    // draw border per contour - no need to scale
+    fn render_border(&self, canvas: &mut Canvas) {
+        if self.border_color[3] == 0. && self.border_paint.is_none() {
+            return;
+        }
+        let color = self.border_color;
+        canvas.set_color([color[0], color[1], color[2]]);
+        let alpha = match &self.border_paint {
+            Some(paint) => {
+                canvas.set_paint(paint);
+                1.
             }
+            None => color[3],
+        };
+        for contour in self.contours.iter().filter(|x| !x.is_empty()) {
+            let mut border_vertices = contour.clone();
+            border_vertices.push(border_vertices[0]);
+            LineSegs2f::new(border_vertices, color).stroke(canvas, alpha);
+        }
+    }
 
-            current_y += 1;
+    // same style, different contours
+    fn with_contours(&self, contours: Vec<Vec<Point2f>>) -> Polygon2f {
+        Polygon2f {
+            contours,
+            color: self.color,
+            border_color: self.border_color,
+            antialias: self.antialias,
+            fill_rule: self.fill_rule,
+            paint: self.paint.clone(),
+            border_paint: self.border_paint.clone(),
+            blend_mode: self.blend_mode,
         }
-        // draw border - no need to scale
-        if self.border_color[3] != 0. {
-            let mut border_vertices = self.vertices.clone();
-            border_vertices.push(border_vertices[0]);
-            LineSegs2f {
-                vertices: border_vertices,
-                color: self.border_color,
-            }.render(canvas);
+    }
+
+    // keep the paints in place on the object mapped by mat
+    fn transform_paints(mut self, mat: Mat3x3f) -> Polygon2f {
+        self.paint = self.paint.map(|x| x.transformed(mat));
+        self.border_paint = self.border_paint.map(|x| x.transformed(mat));
+        self
+    }
+
+    fn map_vertices(&self, f: impl Fn(Point2f) -> Point2f) -> Polygon2f {
+        self.with_contours(
+            self.contours
+                .iter()
+                .map(|contour| contour.iter().map(|x| f(*x)).collect())
+                .collect(),
+        )
+    }
+
+    // single convex contour
+    pub fn is_convex(&self) -> bool {
+        self.contours.len() == 1 && clip::is_convex(&self.contours[0])
+    }
+
+    pub fn contains(&self, point: Point2f) -> bool {
+        clip::contains(point, &self.contours, self.fill_rule)
+    }
+
+    // intersection with a region as one even-odd polygon,
+    // the pieces of every contour pair together make the result
+    // see clip::clip_polygon, None if it gave up on any pair
+    // nonzero polygons are turned to their even-odd outline first,
+    // as overlaps would cancel out in even-odd pieces
+    pub fn clip(&self, region: &Polygon2f) -> Option<Polygon2f> {
+        let even_odd = |polygon2f: &Polygon2f| match polygon2f.fill_rule {
+            FillRule::NonZero => clip::nonzero_outline(&polygon2f.contours),
+            FillRule::EvenOdd => polygon2f.contours.clone(),
+        };
+        let region_contours = even_odd(region);
+        let mut contours = Vec::new();
+        for contour in even_odd(self).iter() {
+            for region_contour in region_contours.iter() {
+                contours.extend(clip::clip_polygon(contour, region_contour)?);
+            }
         }
+        let mut polygon2f = self.with_contours(contours);
+        polygon2f.fill_rule = FillRule::EvenOdd;
+        Some(polygon2f)
     }
-}
 
-impl Polygon2f {
-    pub fn new(vertices: Vec<Point2f>, color: [f32; 4], border_color: [f32; 4]) -> Polygon2f {
-        Polygon2f { vertices, color, border_color }
+    // split with the line through a and b, returns (left, right)
+    // left means cross(b - a, p - a) >= 0, a concave contour
+    // cut into several pieces gives a contour for every piece
+    pub fn split_by_line(&self, a: Point2f, b: Point2f) -> (Polygon2f, Polygon2f) {
+        let side = |a, b| {
+            self.with_contours(
+                self.contours
+                    .iter()
+                    .flat_map(|contour| clip::split_half_plane(contour, a, b))
+                    .collect(),
+            )
+        };
+        (side(a, b), side(b, a))
+    }
+
+    // text format, see GraphicObjects::from_strs
+    pub fn to_str(&self) -> String {
+        let mut tokens: Vec<String> = vec!["P".to_string()];
+        if self.fill_rule == FillRule::NonZero {
+            tokens.push("nonzero".to_string());
+        }
+        if self.blend_mode != BlendMode::SourceOver {
+            tokens.push("blend".to_string());
+            tokens.push(self.blend_mode.to_str().to_string());
+        }
+        if let Some(paint) = &self.paint {
+            tokens.push("fill".to_string());
+            tokens.extend(paint.to_tokens());
+        }
+        if let Some(paint) = &self.border_paint {
+            tokens.push("border".to_string());
+            tokens.extend(paint.to_tokens());
+        }
+        tokens.extend(
+            self.border_color
+                .iter()
+                .chain(self.color.iter())
+                .map(|x| x.to_string()),
+        );
+        for (i, contour) in self.contours.iter().enumerate() {
+            if i != 0 {
+                tokens.push("|".to_string());
+            }
+            for vertex in contour.iter() {
+                tokens.push(vertex.x.to_string());
+                tokens.push(vertex.y.to_string());
+            }
+        }
+        tokens.join(" ")
     }
 
     pub fn from_floats(floats: Vec<f32>) -> Polygon2f {
@@ -207,3 +312,195 @@ impl Polygon2f {
         Polygon2f::new(vertices, color, border_color)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Polygon2f;
+    use crate::algebra::{Mat3x3f, Point2f};
+    use crate::blend::BlendMode;
+    use crate::canvas::Canvas;
+    use crate::graphic_object::{FillRule, GraphicObject, GraphicObjects};
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_contours() {
+        let line = "P nonzero 0 0 0 0 1 1 1 1 0 0 6 0 6 6 0 6 | 2 2 2 4 4 4 4 2";
+        let graphic_objects = GraphicObjects::from_strs(vec![line]);
+        let graphic_object = graphic_objects.into_iter().next().unwrap();
+        let polygon2f = graphic_object
+            .as_any()
+            .downcast_ref::<Polygon2f>()
+            .unwrap();
+        assert_eq!(polygon2f.contours.len(), 2);
+        assert_eq!(polygon2f.fill_rule, FillRule::NonZero);
+        assert_eq!(polygon2f.to_str(), line);
+        // the inner contour goes the other way
+        assert!(polygon2f.contains(Point2f::from_floats(1., 1.)));
+        assert!(!polygon2f.contains(Point2f::from_floats(3., 3.)));
+
+        for antialias in [false, true].iter() {
+            let mut canvas = Canvas::new((6, 6), 1.);
+            let mut polygon2f = polygon2f.clone();
+            polygon2f.antialias = Some(*antialias);
+            polygon2f.render(&mut canvas);
+            assert_eq!(canvas.data[(6 + 1) * 3], 255);
+            assert_eq!(canvas.data[(3 * 6 + 3) * 3], 0);
+        }
+    }
+
+    #[test]
+    fn test_paint() {
+        // red to blue from left to right, border fades in from the top
+        let line = "p fill linear pad 0 0 4 0 2 0 1 0 0 1 1 0 0 1 1 \
+            border linear pad 0 0 0 4 2 0 1 1 1 0 1 1 1 1 1 \
+            0 0 0 1 0 0 4 0 4 4 0 4";
+        let graphic_objects = GraphicObjects::from_strs(vec![line]);
+        let graphic_object = graphic_objects.into_iter().next().unwrap();
+        let polygon2f = graphic_object
+            .as_any()
+            .downcast_ref::<Polygon2f>()
+            .unwrap();
+        assert!(polygon2f.paint.is_some() && polygon2f.border_paint.is_some());
+        let text = polygon2f.to_str();
+        let parsed = GraphicObjects::from_strs(vec![&text]).into_iter().next().unwrap();
+        assert_eq!(parsed.as_any().downcast_ref::<Polygon2f>().unwrap().to_str(), text);
+
+        let mut canvas = Canvas::new((8, 4), 1.);
+        // the paint moves with the polygon
+        graphic_object.shift(Point2f::from_floats(4., 0.)).render(&mut canvas);
+        let pixel = |x: usize, y: usize, c: usize| canvas.data[(y * 8 + x) * 3 + c];
+        // pixel centers at t = 3/8 and 5/8, away from the border
+        assert_eq!(pixel(5, 2, 0), 159);
+        assert_eq!(pixel(5, 2, 2), 95);
+        assert_eq!(pixel(6, 2, 0), 95);
+        assert_eq!(pixel(6, 2, 2), 159);
+        // border alpha grows downwards
+        assert!(pixel(5, 3, 1) > pixel(5, 0, 1));
+    }
+
+    #[test]
+    fn test_clip() {
+        let line = "P 0 0 0 0 1 1 1 1 0 0 6 0 6 6 0 6 | 2 2 2 4 4 4 4 2";
+        let framed = GraphicObjects::from_strs(vec![line]).into_iter().next().unwrap();
+        let framed = framed.as_any().downcast_ref::<Polygon2f>().unwrap();
+        let region = Polygon2f::new(
+            vec![
+                Point2f::from_floats(3., -1.),
+                Point2f::from_floats(7., -1.),
+                Point2f::from_floats(7., 7.),
+                Point2f::from_floats(3., 7.),
+            ],
+            [1., 1., 1., 1.],
+            [0., 0., 0., 0.],
+        );
+        // the right half of the frame, the hole stays a hole
+        let clipped = framed.clip(&region).unwrap();
+        assert_eq!(clipped.fill_rule, FillRule::EvenOdd);
+        for (x, y, inside) in [(4.5, 1., true), (3.5, 3., false), (1., 1., false)].iter() {
+            assert_eq!(clipped.contains(Point2f::from_floats(*x, *y)), *inside);
+        }
+    }
+
+    #[test]
+    fn test_clip_nonzero() {
+        let square = |x0: f32, y0: f32, x1: f32, y1: f32| {
+            Polygon2f::new(
+                vec![
+                    Point2f::from_floats(x0, y0),
+                    Point2f::from_floats(x1, y0),
+                    Point2f::from_floats(x1, y1),
+                    Point2f::from_floats(x0, y1),
+                ],
+                [1., 1., 1., 1.],
+                [0., 0., 0., 0.],
+            )
+        };
+        // two squares the same way around overlap, even-odd would leave
+        // the overlap out, and a square hole going the other way
+        let line = "P nonzero 0 0 0 0 1 1 1 1 0 0 4 0 4 4 0 4 | 2 2 6 2 6 6 2 6 \
+                    | 4.5 4.5 4.5 5.5 5.5 5.5 5.5 4.5";
+        let polygon = GraphicObjects::from_strs(vec![line]).into_iter().next().unwrap();
+        let polygon = polygon.as_any().downcast_ref::<Polygon2f>().unwrap();
+        let clipped = polygon.clip(&square(1., 1., 7., 7.)).unwrap();
+        let inside = |x: f32, y: f32| clipped.contains(Point2f::from_floats(x, y));
+        assert!(inside(3., 3.));
+        assert!(inside(1.5, 1.5));
+        assert!(inside(4.2, 4.2));
+        assert!(!inside(5., 5.));
+        assert!(!inside(4.5, 1.5));
+        assert!(!inside(0.5, 0.5));
+
+        // one self-intersecting contour, the middle of the star is wound twice
+        let mut star = Polygon2f::new(
+            (0..5)
+                .map(|i| Point2f::from_polar(2., i as f32 * 4. * PI / 5.))
+                .collect(),
+            [1., 1., 1., 1.],
+            [0., 0., 0., 0.],
+        );
+        star.fill_rule = FillRule::NonZero;
+        let clipped = star.clip(&square(-1., -3., 3., 3.)).unwrap();
+        assert!(clipped.contains(Point2f::new()));
+        assert!(clipped.contains(Point2f::from_floats(1., 0.1)));
+        assert!(!clipped.contains(Point2f::from_floats(1.2, 1.2)));
+        assert!(!clipped.contains(Point2f::from_floats(1.9, 1.9)));
+    }
+
+    #[test]
+    fn test_projective() {
+        let square = Polygon2f::new(
+            vec![
+                Point2f::from_floats(0., 0.),
+                Point2f::from_floats(1., 0.),
+                Point2f::from_floats(1., 1.),
+                Point2f::from_floats(0., 1.),
+            ],
+            [1., 1., 1., 1.],
+            [0., 0., 0., 0.],
+        );
+        let mat = Mat3x3f::from_unit_square([
+            Point2f::from_floats(0., 0.),
+            Point2f::from_floats(4., 0.),
+            Point2f::from_floats(3., 2.),
+            Point2f::from_floats(1., 2.),
+        ])
+        .unwrap();
+        let projected = square.projective(mat, Some(0.25));
+        let polygon2f = projected.as_any().downcast_ref::<Polygon2f>().unwrap();
+        // every edge in 4 pieces, on the homography of the edge
+        assert_eq!(polygon2f.contours[0].len(), 16);
+        for (k, vertex) in polygon2f.contours[0].iter().take(4).enumerate() {
+            let expected = mat * Point2f::from_floats(k as f32 / 4., 0.);
+            assert!((*vertex - expected).norm() < 1e-5);
+        }
+        let on_top = mat * Point2f::from_floats(0.5, 1.);
+        assert!((polygon2f.contours[0][10] - on_top).norm() < 1e-5);
+
+        // no subdivision rather than endless
+        for max_len in [0., -1., f32::NAN].iter() {
+            let projected = square.projective(mat, Some(*max_len));
+            let polygon2f = projected.as_any().downcast_ref::<Polygon2f>().unwrap();
+            assert_eq!(polygon2f.contours[0].len(), 4);
+        }
+    }
+
+    #[test]
+    fn test_blend_mode() {
+        let gray = "p 0.5 0.5 0.5 1 0 0 4 0 4 4 0 4";
+        let red = "p blend multiply 1 0 0 1 2 0 4 0 4 4 2 4";
+        let mut canvas = Canvas::new((4, 4), 1.);
+        for line in [gray, red].iter() {
+            for graphic_object in GraphicObjects::from_strs(vec![line]).into_iter() {
+                graphic_object.render(&mut canvas);
+            }
+        }
+        let pixel = |x: usize, y: usize, c: usize| canvas.data[(y * 4 + x) * 3 + c];
+        assert_eq!([pixel(1, 1, 0), pixel(1, 1, 1)], [127, 127]);
+        assert_eq!([pixel(3, 1, 0), pixel(3, 1, 1)], [127, 0]);
+
+        let parsed = GraphicObjects::from_strs(vec![red]).into_iter().next().unwrap();
+        let polygon2f = parsed.as_any().downcast_ref::<Polygon2f>().unwrap();
+        assert_eq!(polygon2f.blend_mode, BlendMode::Multiply);
+        assert!(polygon2f.to_str().contains("blend multiply"));
+    }
+}
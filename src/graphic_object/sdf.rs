@@ -0,0 +1,376 @@
+use std::any::Any;
+use std::f32::consts::PI;
+
+use crate::algebra::{Point2f, Mat2x2f, Mat3x3f};
+use crate::blend::BlendMode;
+use crate::canvas::Canvas;
+use super::GraphicObject;
+
+// shapes given by a signed distance, negative inside
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SdfShape {
+    Circle {
+        center: Point2f,
+        r: f32,
+    },
+    // corners rounded by radius, clamped to the half size
+    RoundedRect {
+        center: Point2f,
+        half_size: Point2f,
+        radius: f32,
+    },
+    // segment from a to b thickened by r
+    Capsule {
+        a: Point2f,
+        b: Point2f,
+        r: f32,
+    },
+    // part of the ring between the radii, from start counterclockwise by sweep
+    RingSector {
+        center: Point2f,
+        radii: (f32, f32),
+        start: f32,
+        sweep: f32,
+    },
+}
+
+fn segment_distance(p: Point2f, a: Point2f, b: Point2f) -> f32 {
+    let pa = p - a;
+    let ba = b - a;
+    let len2 = ba.dotx(ba);
+    let h = if len2 == 0. {
+        0.
+    } else {
+        (pa.dotx(ba) / len2).clamp(0., 1.)
+    };
+    (pa - ba * h).norm()
+}
+
+impl SdfShape {
+    pub fn distance(&self, p: Point2f) -> f32 {
+        match *self {
+            SdfShape::Circle { center, r } => (p - center).norm() - r,
+            SdfShape::RoundedRect {
+                center,
+                half_size,
+                radius,
+            } => {
+                let radius = radius.clamp(0., half_size.x.min(half_size.y));
+                let d = p - center;
+                let qx = d.x.abs() - half_size.x + radius;
+                let qy = d.y.abs() - half_size.y + radius;
+                Point2f::from_floats(qx.max(0.), qy.max(0.)).norm() + qx.max(qy).min(0.) - radius
+            }
+            SdfShape::Capsule { a, b, r } => segment_distance(p, a, b) - r,
+            SdfShape::RingSector {
+                center,
+                radii,
+                start,
+                sweep,
+            } => {
+                let (r0, r1) = (radii.0.min(radii.1), radii.0.max(radii.1));
+                let d = p - center;
+                // distance to the full ring
+                let ring = (d.norm() - (r0 + r1) / 2.).abs() - (r1 - r0) / 2.;
+                if sweep.abs() >= 2. * PI {
+                    return ring;
+                }
+                let (start, sweep) = if sweep < 0. {
+                    (start + sweep, -sweep)
+                } else {
+                    (start, sweep)
+                };
+                let cap = |theta: f32| {
+                    segment_distance(
+                        d,
+                        Point2f::from_polar(r0, theta),
+                        Point2f::from_polar(r1, theta),
+                    )
+                };
+                let caps = cap(start).min(cap(start + sweep));
+                let angle = (d.y.atan2(d.x) - start).rem_euclid(2. * PI);
+                if angle > sweep {
+                    // the closest point is on one of the straight ends
+                    caps
+                } else if ring > 0. {
+                    ring
+                } else {
+                    ring.max(-caps)
+                }
+            }
+        }
+    }
+
+    fn center(&self) -> Point2f {
+        match *self {
+            SdfShape::Circle { center, .. } => center,
+            SdfShape::RoundedRect { center, .. } => center,
+            SdfShape::Capsule { a, b, .. } => (a + b) / 2.,
+            SdfShape::RingSector { center, .. } => center,
+        }
+    }
+
+    // (min, max) corners containing the shape
+    fn bounds(&self) -> (Point2f, Point2f) {
+        let around = |center: Point2f, r: Point2f| (center - r, center + r);
+        match *self {
+            SdfShape::Circle { center, r } => around(center, Point2f::from_floats(r, r)),
+            SdfShape::RoundedRect {
+                center, half_size, ..
+            } => around(center, half_size),
+            SdfShape::Capsule { a, b, r } => (
+                Point2f::from_floats(a.x.min(b.x) - r, a.y.min(b.y) - r),
+                Point2f::from_floats(a.x.max(b.x) + r, a.y.max(b.y) + r),
+            ),
+            SdfShape::RingSector { center, radii, .. } => {
+                let r = radii.0.max(radii.1);
+                around(center, Point2f::from_floats(r, r))
+            }
+        }
+    }
+}
+
+// analytic coverage from the distance at every pixel center,
+// smooth at any zoom without tessellation
+#[derive(Clone, Debug)]
+pub struct Sdf2f {
+    pub shape: SdfShape,
+    pub color: [f32; 4],
+    // band of outline_width centered on the edge, drawn over the fill
+    pub outline_color: [f32; 4],
+    pub outline_width: f32,
+    // extra width of the soft edge, 0 for a plain anti-aliased edge
+    // outline_width and feather are in shape units and scale with it
+    pub feather: f32,
+    // from shape space to object space
+    pub transform: Mat3x3f,
+    pub blend_mode: BlendMode,
+}
+
+impl Sdf2f {
+    pub fn new(shape: SdfShape, color: [f32; 4]) -> Sdf2f {
+        Sdf2f {
+            shape,
+            color,
+            outline_color: [0., 0., 0., 0.],
+            outline_width: 0.,
+            feather: 0.,
+            transform: Mat3x3f::identity(),
+            blend_mode: BlendMode::SourceOver,
+        }
+    }
+
+    fn transformed(&self, mat: Mat3x3f) -> Sdf2f {
+        Sdf2f {
+            transform: mat * self.transform,
+            ..self.clone()
+        }
+    }
+
+    // coverage of a ramp 1 pixel plus feather wide around the edge
+    // d and feather are in pixels
+    fn coverage(d: f32, feather: f32) -> f32 {
+        (0.5 - d / (1. + feather)).clamp(0., 1.)
+    }
+}
+
+impl GraphicObject for Sdf2f {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn shift(&self, dp: Point2f) -> Box<dyn GraphicObject> {
+        Box::new(self.transformed(Mat3x3f {
+            x3: dp.x,
+            y3: dp.y,
+            ..Mat3x3f::identity()
+        }))
+    }
+
+    fn rotate(&self, rotate_mat: Mat2x2f) -> Box<dyn GraphicObject> {
+        Box::new(self.transformed(Mat3x3f::from_affine(rotate_mat, Point2f::new())))
+    }
+
+    fn zoom(&self, k: f32) -> Box<dyn GraphicObject> {
+        Box::new(self.transformed(Mat3x3f {
+            x1: k,
+            y2: k,
+            ..Mat3x3f::identity()
+        }))
+    }
+
+    fn shear(&self, k: f32) -> Box<dyn GraphicObject> {
+        Box::new(self.transformed(Mat3x3f {
+            x2: k,
+            ..Mat3x3f::identity()
+        }))
+    }
+
+    // exact, there are no edges to subdivide
+    fn projective(&self, mat: Mat3x3f, _max_len: Option<f32>) -> Box<dyn GraphicObject> {
+        Box::new(self.transformed(mat))
+    }
+
+    // approximated by the affine map closest to f at the shape center
+    fn warp(&self, f: &dyn Fn(Point2f) -> Point2f, _tolerance: f32) -> Box<dyn GraphicObject> {
+        let c = self.transform * self.shape.center();
+        let h = c.x.abs().max(c.y.abs()).max(1.) * 1e-3;
+        let (hx, hy) = (Point2f::from_floats(h, 0.), Point2f::from_floats(0., h));
+        let dx = (f(c + hx) - f(c - hx)) / (2. * h);
+        let dy = (f(c + hy) - f(c - hy)) / (2. * h);
+        let jacobian = Mat2x2f {
+            x1: dx.x,
+            x2: dy.x,
+            y1: dx.y,
+            y2: dy.y,
+        };
+        let fc = f(c);
+        Box::new(self.transformed(Mat3x3f::from_affine(jacobian, fc - jacobian * c)))
+    }
+
+    fn render(&self, canvas: &mut Canvas) {
+        canvas.set_blend_mode(self.blend_mode);
+        let to_pixel = Mat3x3f {
+            x1: canvas.scaler,
+            y2: canvas.scaler,
+            ..Mat3x3f::identity()
+        } * self.transform;
+        let to_shape = match to_pixel.inverse() {
+            Some(x) => x,
+            None => return,
+        };
+        // pixel box of the shape grown by the soft part
+        let (lo, hi) = self.shape.bounds();
+        let grow = self.outline_width / 2. + self.feather;
+        let lo = lo - Point2f::from_floats(grow, grow);
+        let hi = hi + Point2f::from_floats(grow, grow);
+        let corners = [
+            to_pixel * lo,
+            to_pixel * Point2f::from_floats(hi.x, lo.y),
+            to_pixel * hi,
+            to_pixel * Point2f::from_floats(lo.x, hi.y),
+        ];
+        let clip = canvas.get_clip();
+        let min_x = corners.iter().fold(f32::INFINITY, |m, p| m.min(p.x));
+        let max_x = corners.iter().fold(f32::NEG_INFINITY, |m, p| m.max(p.x));
+        let min_y = corners.iter().fold(f32::INFINITY, |m, p| m.min(p.y));
+        let max_y = corners.iter().fold(f32::NEG_INFINITY, |m, p| m.max(p.y));
+        // one more pixel for the anti-aliased ramp, NaN from a projective
+        // horizon ends up as an empty range
+        let x0 = ((min_x - 1.).floor().max(clip.0 as f32) as i32).min(clip.2);
+        let x1 = ((max_x + 2.).ceil().max(clip.0 as f32) as i32).min(clip.2);
+        let y0 = ((min_y - 1.).floor().max(clip.1 as f32) as i32).min(clip.3);
+        let y1 = ((max_y + 2.).ceil().max(clip.1 as f32) as i32).min(clip.3);
+        let draw_outline = self.outline_width > 0. && self.outline_color[3] > 0.;
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let p = Point2f::from_floats(x as f32 + 0.5, y as f32 + 0.5);
+                let q = to_shape * p;
+                let d = self.shape.distance(q);
+                // shape units per pixel along the distance gradient
+                let jx = to_shape * (p + Point2f::from_floats(1., 0.)) - q;
+                let jy = to_shape * (p + Point2f::from_floats(0., 1.)) - q;
+                let h = 1e-3 * (jx.norm() + jy.norm());
+                let gradient = Point2f::from_floats(
+                    self.shape.distance(q + Point2f::from_floats(h, 0.)) - d,
+                    self.shape.distance(q + Point2f::from_floats(0., h)) - d,
+                ) / h;
+                let mut scale = Point2f::from_floats(gradient.dotx(jx), gradient.dotx(jy)).norm();
+                if scale <= 0. || !scale.is_finite() {
+                    scale = jx.crossx(jy).abs().sqrt();
+                }
+                let feather = self.feather / scale;
+                if self.color[3] > 0. {
+                    let coverage = Sdf2f::coverage(d / scale, feather);
+                    if coverage > 0. {
+                        canvas.set_color([self.color[0], self.color[1], self.color[2]]);
+                        canvas.putpixel(x, y, self.color[3] * coverage);
+                    }
+                }
+                if draw_outline {
+                    let band = (d.abs() - self.outline_width / 2.) / scale;
+                    let coverage = Sdf2f::coverage(band, feather);
+                    if coverage > 0. {
+                        let color = self.outline_color;
+                        canvas.set_color([color[0], color[1], color[2]]);
+                        canvas.putpixel(x, y, color[3] * coverage);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Sdf2f, SdfShape};
+    use crate::algebra::Point2f;
+    use crate::canvas::Canvas;
+    use crate::graphic_object::GraphicObject;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_sdf_distance() {
+        let eps = 1e-5;
+        let p = Point2f::from_floats;
+        let rect = SdfShape::RoundedRect {
+            center: p(0., 0.),
+            half_size: p(3., 2.),
+            radius: 1.,
+        };
+        assert!((rect.distance(p(0., 0.)) + 2.).abs() < eps);
+        assert!((rect.distance(p(5., 0.)) - 2.).abs() < eps);
+        // around the rounded corner
+        assert!((rect.distance(p(3., 2.)) - (2f32.sqrt() - 1.)).abs() < eps);
+        let capsule = SdfShape::Capsule {
+            a: p(0., 0.),
+            b: p(4., 0.),
+            r: 1.,
+        };
+        assert!((capsule.distance(p(2., 3.)) - 2.).abs() < eps);
+        assert!((capsule.distance(p(-2., 0.)) - 1.).abs() < eps);
+        // upper half of a ring from 2 to 4
+        let sector = SdfShape::RingSector {
+            center: p(0., 0.),
+            radii: (2., 4.),
+            start: 0.,
+            sweep: PI,
+        };
+        assert!((sector.distance(p(0., 3.)) + 1.).abs() < eps);
+        assert!((sector.distance(p(0., 5.)) - 1.).abs() < eps);
+        assert!((sector.distance(p(3., -1.)) - 1.).abs() < eps);
+        assert!((sector.distance(p(3.5, 0.1)) + 0.1).abs() < eps);
+    }
+
+    #[test]
+    fn test_sdf_render() {
+        let circle = SdfShape::Circle {
+            center: Point2f::from_floats(4., 4.),
+            r: 2.,
+        };
+        let mut sdf2f = Sdf2f::new(circle, [1., 1., 1., 1.]);
+        let mut canvas = Canvas::new((8, 8), 1.);
+        sdf2f.render(&mut canvas);
+        let pixel = |canvas: &Canvas, x: usize, y: usize, c: usize| {
+            canvas.data[(y * canvas.get_size().0 as usize + x) * 3 + c]
+        };
+        assert_eq!(pixel(&canvas, 4, 4, 0), 255);
+        assert_eq!(pixel(&canvas, 0, 4, 0), 0);
+        // center at distance 1.5, half way through the ramp at the edge
+        let edge = pixel(&canvas, 5, 2, 0);
+        assert!(edge > 50 && edge < 200);
+
+        // outline and feather grow with the zoom, so the ramp does too
+        sdf2f.outline_color = [1., 0., 0., 1.];
+        sdf2f.outline_width = 0.5;
+        sdf2f.feather = 0.5;
+        let mut canvas = Canvas::new((16, 16), 1.);
+        sdf2f.zoom(2.).render(&mut canvas);
+        assert_eq!(pixel(&canvas, 8, 8, 1), 255);
+        // next to the edge, the outline tints it red
+        for x in [11, 12].iter() {
+            assert!(pixel(&canvas, *x, 8, 0) > pixel(&canvas, *x, 8, 1) + 100);
+        }
+        assert_eq!(pixel(&canvas, 15, 8, 0), 0);
+    }
+}
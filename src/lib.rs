@@ -1,4 +1,10 @@
 pub mod algebra;
+pub mod blend;
 pub mod canvas;
+pub mod clip;
 pub mod graphic_object;
 pub mod fsd;
+pub mod gamma;
+pub mod mask;
+pub mod paint;
+pub mod rasterizer;
@@ -0,0 +1,176 @@
+use crate::canvas::{Canvas, PixelFormat};
+use crate::graphic_object::{GraphicObject, GraphicObjects};
+
+// 8 bit coverage of every pixel, multiplies the alpha of draws
+// while pushed on a canvas
+#[derive(Clone, Debug)]
+pub struct Mask {
+    // one byte per pixel, row by row
+    pub data: Vec<u8>,
+    size: (i32, i32),
+    scaler: f32,
+    antialias: bool,
+    line_width: f32,
+}
+
+impl Mask {
+    // sized and scaled as what canvas renders, nothing passes
+    // until drawn into
+    pub fn new(canvas: &Canvas) -> Mask {
+        let size = canvas.get_size();
+        Mask {
+            data: vec![0; (size.0.max(0) * size.1.max(0)) as usize],
+            size,
+            scaler: canvas.scaler,
+            antialias: canvas.antialias,
+            line_width: canvas.line_width,
+        }
+    }
+
+    pub fn from_object(canvas: &Canvas, graphic_object: &dyn GraphicObject) -> Mask {
+        let mut mask = Mask::new(canvas);
+        mask.draw(graphic_object);
+        mask
+    }
+
+    // adds the alpha graphic_object would draw, colors are dropped
+    pub fn draw(&mut self, graphic_object: &dyn GraphicObject) {
+        let size = self.size;
+        let mut canvas = Canvas::from_buffer(
+            &mut self.data,
+            size,
+            size.0.max(0) as usize,
+            PixelFormat::Alpha8,
+            self.scaler,
+        );
+        canvas.antialias = self.antialias;
+        canvas.line_width = self.line_width;
+        graphic_object.render(&mut canvas);
+    }
+
+    // draw of every object, as for text from fsd
+    pub fn draw_all(&mut self, graphic_objects: &GraphicObjects) {
+        for graphic_object in graphic_objects.iter() {
+            self.draw(graphic_object.as_ref());
+        }
+    }
+
+    pub fn get_size(&self) -> (i32, i32) {
+        self.size
+    }
+
+    // coverage in [0, 1] of pixel (x, y), in range
+    #[inline]
+    pub fn coverage(&self, x: i32, y: i32) -> f32 {
+        self.data[(y * self.size.0 + x) as usize] as f32 / 255.
+    }
+
+    // passes what self blocks
+    pub fn invert(&self) -> Mask {
+        Mask {
+            data: self.data.iter().map(|x| 255 - x).collect(),
+            size: self.size,
+            scaler: self.scaler,
+            antialias: self.antialias,
+            line_width: self.line_width,
+        }
+    }
+
+    // passes what both pass, coverages multiplied
+    pub fn intersect(&self, other: &Mask) -> Mask {
+        assert_eq!(self.size, other.size, "mask size mismatch");
+        Mask {
+            data: self
+                .data
+                .iter()
+                .zip(other.data.iter())
+                .map(|(a, b)| ((*a as u32 * *b as u32 + 127) / 255) as u8)
+                .collect(),
+            size: self.size,
+            scaler: self.scaler,
+            antialias: self.antialias,
+            line_width: self.line_width,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Mask;
+    use crate::algebra::Point2f;
+    use crate::canvas::{Canvas, DownsampleFilter};
+    use crate::fsd::fsd;
+    use crate::graphic_object::{LineSegs2f, Polygon2f};
+
+    #[test]
+    fn test_mask() {
+        let rect = |x0: f32, y0: f32, x1: f32, y1: f32| {
+            Polygon2f::new(
+                vec![
+                    Point2f::from_floats(x0, y0),
+                    Point2f::from_floats(x1, y0),
+                    Point2f::from_floats(x1, y1),
+                    Point2f::from_floats(x0, y1),
+                ],
+                [1., 1., 1., 1.],
+                [0., 0., 0., 0.],
+            )
+        };
+        let mut canvas = Canvas::new((4, 4), 1.);
+        let left = Mask::from_object(&canvas, &rect(0., 0., 2., 4.));
+        let top = Mask::from_object(&canvas, &rect(0., 0., 4., 2.));
+        assert_eq!(left.intersect(&top).data.iter().filter(|x| **x == 255).count(), 4);
+
+        // nested masks intersect, the right half of the top half is left
+        canvas.push_mask(&top);
+        canvas.push_mask(&left.invert());
+        canvas.set_color([1., 1., 1.]);
+        for y in 0..4 {
+            canvas.fill_span(y, 0, 4, 1.);
+        }
+        canvas.pop_mask();
+        canvas.pop_mask();
+        // half covered, mask coverage multiplies alpha
+        let mut half = Mask::new(&canvas);
+        half.data[12] = 128;
+        canvas.push_mask(&half);
+        canvas.fill_span(3, 0, 4, 1.);
+        canvas.pop_mask();
+        let pixel = |x: usize, y: usize| canvas.data[(y * 4 + x) * 3];
+        for y in 0..3 {
+            for x in 0..4 {
+                let expected = if x >= 2 && y < 2 { 255 } else { 0 };
+                assert_eq!(pixel(x, y), expected, "{} {}", x, y);
+            }
+        }
+        assert_eq!(pixel(0, 3), 128);
+        assert_eq!(pixel(1, 3), 0);
+    }
+
+    #[test]
+    fn test_draw_all() {
+        // glyphs of fsd are in the unit square, 20 pixels wide here
+        let canvas = Canvas::new((1, 1), 20.);
+        let mut mask = Mask::new(&canvas);
+        mask.draw_all(&fsd('8'));
+        // the top and the bottom bar, not the hole between
+        assert!(mask.coverage(10, 3) > 0.5);
+        assert!(mask.coverage(10, 17) > 0.5);
+        assert_eq!(mask.coverage(0, 0), 0.);
+        assert_eq!(mask.coverage(10, 6), 0.);
+    }
+
+    #[test]
+    fn test_line_width() {
+        // lines of a supersampled mask are as wide as on its canvas,
+        // 4 times the samples on a side cover 16 times as many
+        let line = LineSegs2f::from_floats(vec![1., 1., 1., 1., 0.5, 1.5, 7.5, 5.]);
+        let sum = |canvas: &Canvas| {
+            let mask = Mask::from_object(canvas, &line);
+            mask.data.iter().map(|x| *x as i32).sum::<i32>()
+        };
+        let plain = sum(&Canvas::new((8, 8), 1.));
+        let supersampled = sum(&Canvas::new_supersampled((8, 8), 1., 4, DownsampleFilter::Box));
+        assert!((supersampled - plain * 16).abs() < plain, "{} {}", supersampled, plain);
+    }
+}
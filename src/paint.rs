@@ -0,0 +1,540 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use crate::algebra::{Mat3x3f, Point2f};
+use crate::canvas::Canvas;
+
+// rgba from c0 at k = 0 to c1 at k = 1, interpolated premultiplied
+// so transparent ends do not darken the colors in between
+pub fn mix_colors(c0: [f32; 4], c1: [f32; 4], k: f32) -> [f32; 4] {
+    let alpha = c0[3] + (c1[3] - c0[3]) * k;
+    let mut color = [0., 0., 0., alpha];
+    if alpha > 0. {
+        for (c, (a, b)) in color.iter_mut().zip(c0.iter().zip(c1.iter())).take(3) {
+            *c = (a * c0[3] + (b * c1[3] - a * c0[3]) * k) / alpha;
+        }
+    }
+    color
+}
+
+// how t outside of [0, 1] is mapped back
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Spread {
+    // the end colors extend forever
+    Pad,
+    // starts over from 0
+    Repeat,
+    // goes back and forth
+    Reflect,
+}
+
+impl Spread {
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            Spread::Pad => t.clamp(0., 1.),
+            Spread::Repeat => t - t.floor(),
+            Spread::Reflect => {
+                let folded = t.rem_euclid(2.);
+                if folded > 1. {
+                    2. - folded
+                } else {
+                    folded
+                }
+            }
+        }
+    }
+
+    // texel index for images of n texels
+    fn apply_index(self, i: i32, n: i32) -> i32 {
+        match self {
+            Spread::Pad => i.clamp(0, n - 1),
+            Spread::Repeat => i.rem_euclid(n),
+            Spread::Reflect => {
+                let folded = i.rem_euclid(2 * n);
+                if folded >= n {
+                    2 * n - 1 - folded
+                } else {
+                    folded
+                }
+            }
+        }
+    }
+
+    fn to_str(self) -> &'static str {
+        match self {
+            Spread::Pad => "pad",
+            Spread::Repeat => "repeat",
+            Spread::Reflect => "reflect",
+        }
+    }
+
+    fn from_str(string: &str) -> Spread {
+        match string {
+            "pad" => Spread::Pad,
+            "repeat" => Spread::Repeat,
+            "reflect" => Spread::Reflect,
+            _ => panic!("Format error"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Sampling {
+    Nearest,
+    Bilinear,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GradientShape {
+    // t goes from 0 at the first point to 1 at the second
+    Linear(Point2f, Point2f),
+    // center and radius, t is the distance from the center over radius
+    Radial(Point2f, f32),
+    // center and start angle, t is the angle swept counterclockwise
+    // from the start angle over a full turn
+    Conic(Point2f, f32),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Gradient {
+    pub shape: GradientShape,
+    // (offset, rgba) sorted by offset
+    pub stops: Vec<(f32, [f32; 4])>,
+    pub spread: Spread,
+}
+
+impl Gradient {
+    fn param_at(&self, point: Point2f) -> f32 {
+        match self.shape {
+            GradientShape::Linear(start, end) => {
+                let d = end - start;
+                let len2 = d.dotx(d);
+                if len2 == 0. {
+                    return 0.;
+                }
+                (point - start).dotx(d) / len2
+            }
+            GradientShape::Radial(center, r) => {
+                if r == 0. {
+                    return 1.;
+                }
+                (point - center).norm() / r
+            }
+            GradientShape::Conic(center, angle) => {
+                let d = point - center;
+                ((d.y.atan2(d.x) - angle) / (2. * PI)).rem_euclid(1.)
+            }
+        }
+    }
+
+    pub fn color_at(&self, point: Point2f) -> [f32; 4] {
+        let t = self.spread.apply(self.param_at(point));
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return [0.; 4],
+        };
+        if t <= first.0 {
+            return first.1;
+        }
+        if t >= last.0 {
+            return last.1;
+        }
+        let i = self.stops.iter().position(|x| x.0 > t).unwrap();
+        let (o0, c0) = self.stops[i - 1];
+        let (o1, c1) = self.stops[i];
+        mix_colors(c0, c1, (t - o0) / (o1 - o0))
+    }
+}
+
+// image in paint space, texel (i, j) covers [i, i + 1] x [j, j + 1]
+#[derive(Clone, PartialEq, Debug)]
+pub struct Pattern {
+    pub size: (i32, i32),
+    // rgba8 rows, not premultiplied, shared between clones
+    pub data: Arc<Vec<u8>>,
+    // applies on both axes outside of the image
+    pub spread: Spread,
+    pub sampling: Sampling,
+}
+
+impl Pattern {
+    pub fn from_rgba(
+        size: (i32, i32),
+        data: Vec<u8>,
+        spread: Spread,
+        sampling: Sampling,
+    ) -> Pattern {
+        // in usize, large sizes would overflow i32 before the check
+        let len = (size.0.max(0) as usize)
+            .checked_mul(size.1.max(0) as usize)
+            .and_then(|x| x.checked_mul(4));
+        if size.0 <= 0 || size.1 <= 0 || len != Some(data.len()) {
+            panic!("pattern size mismatch");
+        }
+        Pattern {
+            size,
+            data: Arc::new(data),
+            spread,
+            sampling,
+        }
+    }
+
+    // copy of the finalized output of a canvas
+    pub fn from_canvas(canvas: &Canvas, spread: Spread, sampling: Sampling) -> Pattern {
+        Pattern::from_rgba(
+            canvas.get_output_size(),
+            canvas.get_straight(),
+            spread,
+            sampling,
+        )
+    }
+
+    fn texel(&self, x: i32, y: i32) -> [f32; 4] {
+        let x = self.spread.apply_index(x, self.size.0);
+        let y = self.spread.apply_index(y, self.size.1);
+        let pos = ((y * self.size.0 + x) * 4) as usize;
+        let mut color = [0f32; 4];
+        for (c, d) in color.iter_mut().zip(self.data[pos..pos + 4].iter()) {
+            *c = *d as f32 / 255.;
+        }
+        color
+    }
+
+    pub fn color_at(&self, point: Point2f) -> [f32; 4] {
+        match self.sampling {
+            Sampling::Nearest => self.texel(point.x.floor() as i32, point.y.floor() as i32),
+            Sampling::Bilinear => {
+                // texel centers are at +0.5
+                let (x, y) = (point.x - 0.5, point.y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i32, y0 as i32);
+                let taps = [
+                    (self.texel(x0, y0), (1. - fx) * (1. - fy)),
+                    (self.texel(x0 + 1, y0), fx * (1. - fy)),
+                    (self.texel(x0, y0 + 1), (1. - fx) * fy),
+                    (self.texel(x0 + 1, y0 + 1), fx * fy),
+                ];
+                // premultiplied, so transparent texels do not bleed their color
+                let mut color = [0f32; 4];
+                for (texel, w) in taps.iter() {
+                    let k = texel[3] * w;
+                    for (c, t) in color.iter_mut().zip(texel.iter()).take(3) {
+                        *c += t * k;
+                    }
+                    color[3] += k;
+                }
+                let alpha = color[3];
+                if alpha > 0. {
+                    for c in color.iter_mut().take(3) {
+                        *c /= alpha;
+                    }
+                }
+                color
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum PaintKind {
+    Gradient(Gradient),
+    Pattern(Pattern),
+}
+
+// color varying over the drawn area, replaces the solid color of an object
+#[derive(Clone, PartialEq, Debug)]
+pub struct Paint {
+    pub kind: PaintKind,
+    // from paint space to object space, follows the object transforms
+    pub transform: Mat3x3f,
+}
+
+impl Paint {
+    pub fn new(kind: PaintKind) -> Paint {
+        Paint {
+            kind,
+            transform: Mat3x3f::identity(),
+        }
+    }
+
+    pub fn linear(
+        start: Point2f,
+        end: Point2f,
+        stops: Vec<(f32, [f32; 4])>,
+        spread: Spread,
+    ) -> Paint {
+        Paint::new(PaintKind::Gradient(Gradient {
+            shape: GradientShape::Linear(start, end),
+            stops,
+            spread,
+        }))
+    }
+
+    pub fn radial(center: Point2f, r: f32, stops: Vec<(f32, [f32; 4])>, spread: Spread) -> Paint {
+        Paint::new(PaintKind::Gradient(Gradient {
+            shape: GradientShape::Radial(center, r),
+            stops,
+            spread,
+        }))
+    }
+
+    pub fn conic(
+        center: Point2f,
+        angle: f32,
+        stops: Vec<(f32, [f32; 4])>,
+        spread: Spread,
+    ) -> Paint {
+        Paint::new(PaintKind::Gradient(Gradient {
+            shape: GradientShape::Conic(center, angle),
+            stops,
+            spread,
+        }))
+    }
+
+    pub fn pattern(pattern: Pattern) -> Paint {
+        Paint::new(PaintKind::Pattern(pattern))
+    }
+
+    // the paint after the object is mapped by mat
+    pub fn transformed(&self, mat: Mat3x3f) -> Paint {
+        Paint {
+            kind: self.kind.clone(),
+            transform: mat * self.transform,
+        }
+    }
+
+    // rgba at a point in paint space
+    pub fn color_at(&self, point: Point2f) -> [f32; 4] {
+        match &self.kind {
+            PaintKind::Gradient(gradient) => gradient.color_at(point),
+            PaintKind::Pattern(pattern) => pattern.color_at(point),
+        }
+    }
+
+    // text format, used by GraphicObjects::from_strs:
+    // linear <spread> x0 y0 x1 y1 <n> (offset r g b a)*n [transform <9 floats>]
+    // radial <spread> cx cy r <n> (offset r g b a)*n [transform <9 floats>]
+    // conic <spread> cx cy angle <n> (offset r g b a)*n [transform <9 floats>]
+    // pattern <spread> <nearest|bilinear> w h (r g b a)*(w*h) [transform <9 floats>]
+    // with pattern channels in 0..255
+    // returns the paint and the number of tokens used
+    pub fn from_tokens(tokens: &[&str]) -> (Paint, usize) {
+        let mut iter = tokens.iter();
+        let mut used = 0;
+        let mut next = || {
+            used += 1;
+            *iter.next().expect("Format error")
+        };
+        let kind = next();
+        let spread = Spread::from_str(next());
+        let mut paint = if kind == "pattern" {
+            let sampling = match next() {
+                "nearest" => Sampling::Nearest,
+                "bilinear" => Sampling::Bilinear,
+                _ => panic!("Format error"),
+            };
+            let mut int = || next().parse::<i32>().expect("int parse fail");
+            let size = (int(), int());
+            let data = (0..size.0 * size.1 * 4)
+                .map(|_| next().parse::<u8>().expect("int parse fail"))
+                .collect();
+            Paint::pattern(Pattern::from_rgba(size, data, spread, sampling))
+        } else {
+            let mut float = || next().parse::<f32>().expect("float parse fail");
+            let shape = match kind {
+                "linear" => GradientShape::Linear(
+                    Point2f::from_floats(float(), float()),
+                    Point2f::from_floats(float(), float()),
+                ),
+                "radial" => {
+                    GradientShape::Radial(Point2f::from_floats(float(), float()), float())
+                }
+                "conic" => GradientShape::Conic(Point2f::from_floats(float(), float()), float()),
+                _ => panic!("Format error"),
+            };
+            let count = float() as usize;
+            let stops = (0..count)
+                .map(|_| (float(), [float(), float(), float(), float()]))
+                .collect();
+            Paint::new(PaintKind::Gradient(Gradient {
+                shape,
+                stops,
+                spread,
+            }))
+        };
+        if tokens.get(used) == Some(&"transform") {
+            let floats: Vec<f32> = tokens
+                .get(used + 1..used + 10)
+                .expect("Format error")
+                .iter()
+                .map(|x| x.parse::<f32>().expect("float parse fail"))
+                .collect();
+            paint.transform = Mat3x3f {
+                x1: floats[0],
+                x2: floats[1],
+                x3: floats[2],
+                y1: floats[3],
+                y2: floats[4],
+                y3: floats[5],
+                z1: floats[6],
+                z2: floats[7],
+                z3: floats[8],
+            };
+            used += 10;
+        }
+        (paint, used)
+    }
+
+    pub fn to_tokens(&self) -> Vec<String> {
+        let mut tokens: Vec<String> = Vec::new();
+        match &self.kind {
+            PaintKind::Gradient(gradient) => {
+                let (kind, floats) = match gradient.shape {
+                    GradientShape::Linear(start, end) => {
+                        ("linear", vec![start.x, start.y, end.x, end.y])
+                    }
+                    GradientShape::Radial(center, r) => ("radial", vec![center.x, center.y, r]),
+                    GradientShape::Conic(center, angle) => {
+                        ("conic", vec![center.x, center.y, angle])
+                    }
+                };
+                tokens.push(kind.to_string());
+                tokens.push(gradient.spread.to_str().to_string());
+                tokens.extend(floats.iter().map(|x| x.to_string()));
+                tokens.push(gradient.stops.len().to_string());
+                for (offset, color) in gradient.stops.iter() {
+                    tokens.push(offset.to_string());
+                    tokens.extend(color.iter().map(|x| x.to_string()));
+                }
+            }
+            PaintKind::Pattern(pattern) => {
+                tokens.push("pattern".to_string());
+                tokens.push(pattern.spread.to_str().to_string());
+                tokens.push(
+                    match pattern.sampling {
+                        Sampling::Nearest => "nearest",
+                        Sampling::Bilinear => "bilinear",
+                    }
+                    .to_string(),
+                );
+                tokens.push(pattern.size.0.to_string());
+                tokens.push(pattern.size.1.to_string());
+                tokens.extend(pattern.data.iter().map(|x| x.to_string()));
+            }
+        }
+        if self.transform != Mat3x3f::identity() {
+            let m = self.transform;
+            tokens.push("transform".to_string());
+            tokens.extend(
+                [m.x1, m.x2, m.x3, m.y1, m.y2, m.y3, m.z1, m.z2, m.z3]
+                    .iter()
+                    .map(|x| x.to_string()),
+            );
+        }
+        tokens
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Paint, Pattern, Sampling, Spread};
+    use crate::algebra::{Mat2x2f, Mat3x3f, Point2f};
+    use crate::canvas::Canvas;
+    use crate::graphic_object::{GraphicObject, Polygon2f};
+
+    #[test]
+    fn test_gradient() {
+        let eps = 1e-5;
+        assert!((Spread::Pad.apply(1.3) - 1.).abs() < eps);
+        assert!((Spread::Repeat.apply(1.3) - 0.3).abs() < eps);
+        assert!((Spread::Repeat.apply(-0.25) - 0.75).abs() < eps);
+        assert!((Spread::Reflect.apply(1.3) - 0.7).abs() < eps);
+        assert!((Spread::Reflect.apply(-0.25) - 0.25).abs() < eps);
+
+        let stops = vec![
+            (0., [1., 0., 0., 1.]),
+            (0.5, [0., 0., 1., 1.]),
+            (1., [0., 0., 1., 0.]),
+        ];
+        let paint = Paint::linear(
+            Point2f::from_floats(0., 0.),
+            Point2f::from_floats(4., 0.),
+            stops.clone(),
+            Spread::Pad,
+        );
+        let color = paint.color_at(Point2f::from_floats(1., 5.));
+        assert!((color[0] - 0.5).abs() < eps && (color[2] - 0.5).abs() < eps);
+        // fading out keeps the hue
+        let color = paint.color_at(Point2f::from_floats(3., 0.));
+        assert!((color[2] - 1.).abs() < eps && (color[3] - 0.5).abs() < eps);
+        assert_eq!(paint.color_at(Point2f::from_floats(-1., 0.)), stops[0].1);
+
+        let paint = Paint::radial(Point2f::from_floats(1., 1.), 2., stops.clone(), Spread::Pad);
+        assert_eq!(paint.color_at(Point2f::from_floats(1., 2.)), stops[1].1);
+        let paint = Paint::conic(Point2f::new(), 0., stops.clone(), Spread::Pad);
+        assert_eq!(paint.color_at(Point2f::from_floats(-1., 0.)), stops[1].1);
+
+        let paint = paint.transformed(Mat3x3f::from_affine(
+            Mat2x2f::from_theta(0.5),
+            Point2f::from_floats(1., 2.),
+        ));
+        let tokens = paint.to_tokens();
+        let tokens: Vec<&str> = tokens.iter().map(|x| x.as_str()).collect();
+        let (parsed, used) = Paint::from_tokens(&tokens);
+        assert_eq!(used, tokens.len());
+        assert_eq!(parsed, paint);
+    }
+
+    #[test]
+    #[should_panic(expected = "pattern size mismatch")]
+    fn test_pattern_too_large() {
+        Pattern::from_rgba((1 << 20, 1 << 20), vec![0; 4], Spread::Pad, Sampling::Nearest);
+    }
+
+    #[test]
+    fn test_pattern() {
+        // 2x2 checker, white on the diagonal
+        let mut tile = Canvas::new((2, 2), 1.);
+        for (x, y) in [(0., 0.), (1., 1.)].iter() {
+            Polygon2f::new(
+                vec![
+                    Point2f::from_floats(*x, *y),
+                    Point2f::from_floats(x + 1., *y),
+                    Point2f::from_floats(x + 1., y + 1.),
+                    Point2f::from_floats(*x, y + 1.),
+                ],
+                [1., 1., 1., 1.],
+                [0., 0., 0., 0.],
+            )
+            .render(&mut tile);
+        }
+        let pattern = Pattern::from_canvas(&tile, Spread::Repeat, Sampling::Nearest);
+        assert_eq!(pattern.color_at(Point2f::from_floats(2.5, 2.5)), [1.; 4]);
+        assert_eq!(pattern.color_at(Point2f::from_floats(-0.5, 0.5)), [0., 0., 0., 1.]);
+        let mut bilinear = pattern.clone();
+        bilinear.sampling = Sampling::Bilinear;
+        let color = bilinear.color_at(Point2f::from_floats(1., 0.5));
+        assert!((color[0] - 0.5).abs() < 1e-5);
+
+        // checker cells of 2 pixels after zooming the polygon
+        let mut polygon2f = Polygon2f::new(
+            vec![
+                Point2f::from_floats(0., 0.),
+                Point2f::from_floats(4., 0.),
+                Point2f::from_floats(4., 4.),
+                Point2f::from_floats(0., 4.),
+            ],
+            [0., 0., 0., 0.],
+            [0., 0., 0., 0.],
+        );
+        polygon2f.paint = Some(Paint::pattern(pattern));
+        let mut canvas = Canvas::new((8, 8), 1.);
+        polygon2f.zoom(2.).render(&mut canvas);
+        let pixel = |x: usize, y: usize| canvas.data[(y * 8 + x) * 3];
+        assert_eq!(pixel(1, 1), 255);
+        assert_eq!(pixel(2, 1), 0);
+        assert_eq!(pixel(5, 5), 255);
+        assert_eq!(pixel(7, 5), 0);
+    }
+}
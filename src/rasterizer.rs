@@ -0,0 +1,457 @@
+use crate::algebra::Point2f;
+use crate::canvas::Canvas;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum FillRule {
+    // odd winding numbers are inside
+    EvenOdd,
+    // any non-zero winding number is inside, as SVG and PostScript
+    NonZero,
+}
+
+impl FillRule {
+    pub fn is_inside(self, winding: i32) -> bool {
+        match self {
+            FillRule::EvenOdd => winding % 2 != 0,
+            FillRule::NonZero => winding != 0,
+        }
+    }
+
+    // fold accumulated fractional winding into coverage in [0, 1]
+    pub fn coverage(self, winding: f32) -> f32 {
+        match self {
+            FillRule::EvenOdd => {
+                let folded = winding.abs() % 2.;
+                if folded > 1. {
+                    2. - folded
+                } else {
+                    folded
+                }
+            }
+            FillRule::NonZero => winding.abs().min(1.),
+        }
+    }
+}
+
+// signed area accumulation buffer covering part of the canvas
+// each cell holds the change of winding coverage from the cell on its left
+struct Accumulator {
+    origin: (i32, i32),
+    width: usize,
+    height: usize,
+    cells: Vec<f32>,
+}
+
+impl Accumulator {
+    fn new(origin: (i32, i32), width: usize, height: usize) -> Accumulator {
+        Accumulator {
+            origin,
+            width,
+            height,
+            // one extra cell on the right takes what falls off the edge
+            cells: vec![0.; (width + 2) * height],
+        }
+    }
+
+    // edge from p0 to p1 in local pixel coordinates
+    // downward edges add coverage, upward edges remove it
+    fn line(&mut self, p0: Point2f, p1: Point2f) {
+        if p0.y == p1.y {
+            return;
+        }
+        let (dir, p0, p1) = if p0.y < p1.y {
+            (1., p0, p1)
+        } else {
+            (-1., p1, p0)
+        };
+        let stride = self.width + 2;
+        let max_x = self.width as f32;
+        let dxdy = (p1.x - p0.x) / (p1.y - p0.y);
+        let y_start = p0.y.max(0.);
+        let y_end = p1.y.min(self.height as f32);
+        if y_start >= y_end {
+            return;
+        }
+        let mut x = p0.x + (y_start - p0.y) * dxdy;
+        for y in y_start as usize..y_end.ceil() as usize {
+            let row = &mut self.cells[y * stride..(y + 1) * stride];
+            let dy = ((y + 1) as f32).min(p1.y) - (y as f32).max(p0.y);
+            let x_next = x + dxdy * dy;
+            let (mut x0, mut x1) = if x < x_next { (x, x_next) } else { (x_next, x) };
+            x = x_next;
+            // the part left of the buffer covers every cell of the row,
+            // its share of the area goes to the first cell whole, the part
+            // right of it covers none and goes to the extra cell
+            let full = dy * dir;
+            if x1 <= 0. {
+                row[0] += full;
+                continue;
+            }
+            if x0 >= max_x {
+                row[self.width] += full;
+                continue;
+            }
+            let mut d = full;
+            let len = x1 - x0;
+            if x0 < 0. {
+                let left = full * -x0 / len;
+                row[0] += left;
+                d -= left;
+                x0 = 0.;
+            }
+            if x1 > max_x {
+                let right = full * (x1 - max_x) / len;
+                row[self.width] += right;
+                d -= right;
+                x1 = max_x;
+            }
+            let x0_floor = x0.floor();
+            let x0i = x0_floor as usize;
+            let x1_ceil = x1.ceil();
+            let x1i = x1_ceil as usize;
+            if x1i <= x0i + 1 {
+                // within one cell, split by the mean x
+                let xmf = 0.5 * (x0 + x1) - x0_floor;
+                row[x0i] += d - d * xmf;
+                row[x0i + 1] += d * xmf;
+            } else {
+                let s = (x1 - x0).recip();
+                let x0f = x0 - x0_floor;
+                let a0 = 0.5 * s * (1. - x0f) * (1. - x0f);
+                let x1f = x1 - x1_ceil + 1.;
+                let am = 0.5 * s * x1f * x1f;
+                row[x0i] += d * a0;
+                if x1i == x0i + 2 {
+                    row[x0i + 1] += d * (1. - a0 - am);
+                } else {
+                    let a1 = s * (1.5 - x0f);
+                    row[x0i + 1] += d * (a1 - a0);
+                    for cell in row[x0i + 2..x1i - 1].iter_mut() {
+                        *cell += d * s;
+                    }
+                    let a2 = a1 + (x1i - x0i - 3) as f32 * s;
+                    row[x1i - 1] += d * (1. - a2 - am);
+                }
+                row[x1i] += d * am;
+            }
+        }
+    }
+
+    // prefix sum each row and blend the coverage
+    fn render(&self, canvas: &mut Canvas, fill_rule: FillRule, alpha: f32) {
+        let stride = self.width + 2;
+        for y in 0..self.height {
+            let mut acc = 0.;
+            let row = &self.cells[y * stride..y * stride + self.width];
+            for (x, cell) in row.iter().enumerate() {
+                acc += cell;
+                let coverage = fill_rule.coverage(acc);
+                if coverage > 1. / 512. {
+                    canvas.putpixel(
+                        self.origin.0 + x as i32,
+                        self.origin.1 + y as i32,
+                        alpha * coverage,
+                    );
+                }
+            }
+        }
+    }
+}
+
+// anti-aliased fill by exact area coverage, all contours are filled together
+// vertices are in pixel coordinates, color must be set already
+pub fn fill_coverage(
+    canvas: &mut Canvas,
+    contours: &[Vec<Point2f>],
+    fill_rule: FillRule,
+    alpha: f32,
+) {
+    let clip = canvas.get_clip();
+    let mut vertices = contours.iter().flatten();
+    let first = match vertices.next() {
+        Some(first) => *first,
+        None => return,
+    };
+    let (mut min, mut max) = (first, first);
+    for vertex in vertices {
+        min.x = min.x.min(vertex.x);
+        min.y = min.y.min(vertex.y);
+        max.x = max.x.max(vertex.x);
+        max.y = max.y.max(vertex.y);
+    }
+    // the buffer only covers the clip, coverage left of it
+    // piles up on its first column
+    let x0 = (min.x.floor().max(clip.0 as f32) as i32).min(clip.2);
+    let y0 = (min.y.floor().max(clip.1 as f32) as i32).min(clip.3);
+    let x1 = (max.x.ceil().max(clip.0 as f32) as i32).min(clip.2);
+    let y1 = (max.y.ceil().max(clip.1 as f32) as i32).min(clip.3);
+    if x0 >= x1 || y0 >= y1 {
+        return;
+    }
+    let mut accumulator = Accumulator::new((x0, y0), (x1 - x0) as usize, (y1 - y0) as usize);
+    let origin = Point2f::from_floats(x0 as f32, y0 as f32);
+    for contour in contours.iter() {
+        let mut last = match contour.last() {
+            Some(last) => *last - origin,
+            None => continue,
+        };
+        for vertex in contour.iter() {
+            let vertex = *vertex - origin;
+            accumulator.line(last, vertex);
+            last = vertex;
+        }
+    }
+    accumulator.render(canvas, fill_rule, alpha);
+}
+
+// scanline edge, in rows sampled at pixel centers
+#[derive(Clone, Copy)]
+struct Edge {
+    // first row after the edge, its center is on or below the lower end
+    end_y: i32,
+    // x at the center of the current row
+    x: f32,
+    dxdy: f32,
+    // +1 downward, -1 upward
+    winding: i32,
+}
+
+// no edge in the bucket list
+const NIL: usize = usize::MAX;
+
+// aliased fill sampling pixel centers, all contours are filled together
+// top-left rule: a center exactly on a top or left edge is inside,
+// on a bottom or right edge is outside, so polygons sharing edges
+// cover every pixel once
+// vertices are in pixel coordinates, color must be set already
+pub fn fill_scanline(
+    canvas: &mut Canvas,
+    contours: &[Vec<Point2f>],
+    fill_rule: FillRule,
+    alpha: f32,
+) {
+    // only the rows of the clip are scanned
+    let clip = canvas.get_clip();
+    let (top, bottom) = (clip.1, clip.3.max(clip.1));
+    // edges are bucketed by their first visible row,
+    // buckets are singly linked lists through next
+    let mut edges: Vec<Edge> = Vec::new();
+    let mut next: Vec<usize> = Vec::new();
+    let mut buckets: Vec<usize> = vec![NIL; (bottom - top) as usize];
+    // rows touched by any edge
+    let (mut min_y, mut max_y) = (bottom, top);
+    for contour in contours.iter() {
+        let mut last = match contour.last() {
+            Some(last) => *last,
+            None => continue,
+        };
+        for vertex in contour.iter() {
+            let (upper, lower, winding) = if vertex.y > last.y {
+                (last, *vertex, 1)
+            } else {
+                (*vertex, last, -1)
+            };
+            last = *vertex;
+            // rows whose center y + 0.5 is in [upper.y, lower.y)
+            let start_y = ((upper.y - 0.5).ceil().max(top as f32) as i32).min(bottom);
+            let end_y = ((lower.y - 0.5).ceil().max(top as f32) as i32).min(bottom);
+            // horizontal edges, edges between two centers
+            // and edges out of the clip are thrown
+            if start_y >= end_y {
+                continue;
+            }
+            min_y = min_y.min(start_y);
+            max_y = max_y.max(end_y);
+            let dxdy = (lower.x - upper.x) / (lower.y - upper.y);
+            let bucket = (start_y - top) as usize;
+            next.push(buckets[bucket]);
+            buckets[bucket] = edges.len();
+            edges.push(Edge {
+                end_y,
+                x: upper.x + (start_y as f32 + 0.5 - upper.y) * dxdy,
+                dxdy,
+                winding,
+            });
+        }
+    }
+
+    // sorted by x, nearly in order from the last row
+    let mut active: Vec<Edge> = Vec::new();
+    for y in min_y..max_y {
+        let mut id = buckets[(y - top) as usize];
+        while id != NIL {
+            active.push(edges[id]);
+            id = next[id];
+        }
+        if active.is_empty() {
+            continue;
+        }
+        active.retain(|x| x.end_y > y);
+        // insertion sort, linear when the order barely changes
+        for i in 1..active.len() {
+            let edge = active[i];
+            let mut j = i;
+            while j > 0 && active[j - 1].x > edge.x {
+                active[j] = active[j - 1];
+                j -= 1;
+            }
+            active[j] = edge;
+        }
+
+        let mut winding = 0;
+        let mut last_x = 0f32;
+        for edge in active.iter_mut() {
+            if fill_rule.is_inside(winding) {
+                // centers x + 0.5 in [last_x, edge.x)
+                canvas.fill_span(
+                    y,
+                    (last_x - 0.5).ceil() as i32,
+                    (edge.x - 0.5).ceil() as i32,
+                    alpha,
+                );
+            }
+            winding += edge.winding;
+            last_x = edge.x;
+            edge.x += edge.dxdy;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{fill_coverage, fill_scanline, FillRule};
+    use crate::algebra::{Point2f, Rect2f};
+    use crate::canvas::Canvas;
+
+    #[test]
+    fn test_fill_coverage() {
+        let mut canvas = Canvas::new((4, 4), 1.);
+        canvas.set_color([1., 1., 1.]);
+        let square: Vec<Point2f> = vec![(0.5, 0.5), (2.5, 0.5), (2.5, 2.5), (0.5, 2.5)]
+            .into_iter()
+            .map(|(x, y)| Point2f::from_floats(x, y))
+            .collect();
+        fill_coverage(&mut canvas, std::slice::from_ref(&square), FillRule::EvenOdd, 1.);
+        let pixel = |x: usize, y: usize| canvas.data[(y * 4 + x) * 3];
+        assert_eq!(pixel(0, 0), 63);
+        assert_eq!(pixel(1, 0), 127);
+        assert_eq!(pixel(1, 1), 255);
+        assert_eq!(pixel(2, 2), 63);
+        assert_eq!(pixel(3, 3), 0);
+        // reversed direction
+        let mut canvas = Canvas::new((4, 4), 1.);
+        canvas.set_color([1., 1., 1.]);
+        let reversed: Vec<Point2f> = square.into_iter().rev().collect();
+        fill_coverage(&mut canvas, &[reversed], FillRule::EvenOdd, 1.);
+        assert_eq!(canvas.data[(4 + 1) * 3], 255);
+        assert_eq!(canvas.data[0], 63);
+    }
+
+    #[test]
+    fn test_fill_coverage_left_bound() {
+        // the slanted edge x = 2y - 1.5 crosses x = 0 within row 0
+        let shape = |dx: f32| -> Vec<Point2f> {
+            vec![(-1.5, 0.), (4., 0.), (4., 2.), (2.5, 2.)]
+                .into_iter()
+                .map(|(x, y)| Point2f::from_floats(x + dx, y))
+                .collect()
+        };
+        let mut canvas = Canvas::new((4, 2), 1.);
+        canvas.set_color([1., 1., 1.]);
+        fill_coverage(&mut canvas, &[shape(0.)], FillRule::EvenOdd, 1.);
+        // 1/16 of pixel (0, 0) is left of the edge
+        assert_eq!(canvas.data[0], 239);
+        // the same as with the whole edge inside the canvas
+        let mut shifted = Canvas::new((6, 2), 1.);
+        shifted.set_color([1., 1., 1.]);
+        fill_coverage(&mut shifted, &[shape(2.)], FillRule::EvenOdd, 1.);
+        for y in 0..2 {
+            for x in 0..4 {
+                assert_eq!(
+                    canvas.data[(y * 4 + x) * 3],
+                    shifted.data[(y * 6 + x + 2) * 3],
+                    "{} {}",
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_fill_rule() {
+        // one ring going around twice
+        let twice: Vec<Point2f> = vec![
+            (0., 0.),
+            (4., 0.),
+            (4., 4.),
+            (0., 4.),
+            (0., 0.),
+            (4., 0.),
+            (4., 4.),
+            (0., 4.),
+        ]
+        .into_iter()
+        .map(|(x, y)| Point2f::from_floats(x, y))
+        .collect();
+        let mut canvas = Canvas::new((4, 4), 1.);
+        canvas.set_color([1., 1., 1.]);
+        fill_coverage(&mut canvas, std::slice::from_ref(&twice), FillRule::EvenOdd, 1.);
+        assert_eq!(canvas.data[(4 + 1) * 3], 0);
+        fill_coverage(&mut canvas, &[twice], FillRule::NonZero, 1.);
+        assert_eq!(canvas.data[(4 + 1) * 3], 255);
+        assert!(FillRule::NonZero.is_inside(-2));
+        assert!(!FillRule::EvenOdd.is_inside(-2));
+    }
+
+    #[test]
+    fn test_fill_scanline_tiling() {
+        // a fan of triangles sharing edges and the center vertex
+        let center = Point2f::from_floats(3.3, 2.7);
+        let ring: Vec<Point2f> = vec![
+            (0.2, 0.1),
+            (3.5, 0.),
+            (7.9, 0.4),
+            (8., 5.5),
+            (4.1, 6.),
+            (0., 5.8),
+        ]
+        .into_iter()
+        .map(|(x, y)| Point2f::from_floats(x, y))
+        .collect();
+        let mut tiled = Canvas::new((8, 6), 1.);
+        tiled.set_color([1., 1., 1.]);
+        for i in 0..ring.len() {
+            let triangle = vec![ring[i], ring[(i + 1) % ring.len()], center];
+            fill_scanline(&mut tiled, &[triangle], FillRule::EvenOdd, 0.5);
+        }
+        let mut whole = Canvas::new((8, 6), 1.);
+        whole.set_color([1., 1., 1.]);
+        fill_scanline(&mut whole, &[ring], FillRule::EvenOdd, 0.5);
+        // no pixel is blended twice or left out
+        assert_eq!(*tiled.data, *whole.data);
+        assert!(whole.data.iter().all(|x| *x == 0 || *x == 127));
+    }
+
+    #[test]
+    fn test_fill_scanline_clip() {
+        // slanted edges start above the clip and end below it
+        let triangle: Vec<Point2f> = vec![(0.3, -2.), (7.6, 1.2), (2.1, 8.3)]
+            .into_iter()
+            .map(|(x, y)| Point2f::from_floats(x, y))
+            .collect();
+        let mut whole = Canvas::new((8, 6), 1.);
+        whole.set_color([1., 1., 1.]);
+        fill_scanline(&mut whole, std::slice::from_ref(&triangle), FillRule::EvenOdd, 1.);
+        let mut clipped = Canvas::new((8, 6), 1.);
+        clipped.set_color([1., 1., 1.]);
+        clipped.push_clip(Rect2f::from_floats(0., 2., 8., 4.));
+        fill_scanline(&mut clipped, &[triangle], FillRule::EvenOdd, 1.);
+        // rows 2 and 3 as without the clip, nothing else
+        let row = 8 * 3;
+        assert_eq!(clipped.data[2 * row..4 * row], whole.data[2 * row..4 * row]);
+        assert!(clipped.data[2 * row..4 * row].contains(&255));
+        assert!(clipped.data[..2 * row].iter().all(|x| *x == 0));
+        assert!(clipped.data[4 * row..].iter().all(|x| *x == 0));
+    }
+}